@@ -0,0 +1,192 @@
+class_name GachaSystemApi
+extends Reference
+# Typed facade over the native `GachaSystem` node (gacha-system crate).
+#
+# Wraps the NativeScript instance so game scripts get autocompletion and type
+# hints. Keep the signatures here in sync with `#[method]`s in `gacha_core.rs`.
+#
+# Signals emitted by the native node:
+#   pity_progress_changed(progress: Dictionary)
+#       after every pull that produced items, same shape as `get_pity_progress()`
+#       for the banner pulled from.
+#   spending_limit_reached(period: String, limit: int, message: String)
+#       when `daily_pull_limit`/`monthly_pull_limit` cut a pull short, `period`
+#       is "daily" or "monthly". The pull still returns the items that were allowed.
+#   confirmation_required(token: String, session_pulls: int)
+#       when a pull is held back after `confirm_spend_after` pulls this session,
+#       show a confirmation dialog and pass `token` to `confirm_spend`.
+#   broadcast_obtained(player_name: String, item: Dictionary)
+#       for each pulled item whose rarity or name is listed in `broadcast`, for
+#       marquee announcements. `player_name` is masked with `broadcast_anonymize`.
+#   configuration_error(message: String)
+#       from `_ready` when `data`/`rarities` are unusable, the node then refuses pulls.
+
+enum Rarity { SSR, SR, R, N }
+
+const _RARITY_NAMES := ["SSR", "SR", "R", "N"]
+
+# Banner made of the node's own `data` and `rarities`, the others come from `banners`.
+const STANDARD_BANNER := "standard"
+
+# `API_VERSION` of `gacha_core.rs` these scripts are written against, the native
+# library must have the same major and at least this minor version.
+const API_VERSION := "2.6"
+
+var native: Node
+
+
+func _init(gacha_node: Node) -> void:
+	assert(gacha_node.has_method("pull"), "not a GachaSystem node")
+	native = gacha_node
+	var problem := _check_api_version()
+	if problem:
+		push_error(problem)
+		assert(false, problem)
+
+
+# Why the native library can't be used with these scripts, empty if it can.
+func _check_api_version() -> String:
+	if not native.has_method("api_version"):
+		return "GachaSystem library predates API versioning, rebuild the .gdnlib binary (scripts expect API %s)" % API_VERSION
+	var native_version: String = native.api_version()
+	var have := native_version.split(".")
+	var want := API_VERSION.split(".")
+	if have[0] != want[0] or int(have[1]) < int(want[1]):
+		return "GachaSystem API mismatch: the .gdnlib binary provides %s but the scripts expect %s, rebuild the library or update the scripts" % [native_version, API_VERSION]
+	return ""
+
+
+func is_disabled() -> bool:
+	return native.is_disabled()
+
+
+# Ids of the banners to pull from, `STANDARD_BANNER` first.
+func list_banners() -> PoolStringArray:
+	return PoolStringArray(native.list_banners())
+
+
+# Pull up to `num` items from `banner_id`, limited by the remaining chances.
+# Each item is a Dictionary with `name`, `rarity` and an optional `weight`.
+# Items named in the node's `disabled_items` are never handed out.
+func pull(banner_id: String, num: int) -> Array:
+	return native.pull(banner_id, num)
+
+
+# Same as `pull`, returns `{ items: Array, reveal: Dictionary or null,
+# confirmation_required: String or null, fairness: Dictionary or null }`.
+func pull_with_reveal(banner_id: String, num: int) -> Dictionary:
+	return native.pull_with_reveal(banner_id, num)
+
+
+# Token of the spend confirmation pulls are waiting for, or null.
+func pending_spend_confirmation():
+	return native.pending_spend_confirmation()
+
+
+func confirm_spend(token: String) -> bool:
+	return native.confirm_spend(token)
+
+
+# `{ banner: String, soft: float, hard: float, ramp_start: int, ramp_progress: float,
+#    pity_count: int, hard_pity_count: int, pity: int, hard_pity: int,
+#    featured_guarantee: bool }` of `banner_id`, empty if there's no such banner.
+#    `soft` and `hard` stay 0 for guarantees `pity_strategy` doesn't make.
+#    `ramp_progress` reaches 1 once the soft pity ramp from pull `ramp_start` raises the
+#    SSR chance, and stays 0 while the ramp is disabled. `featured_guarantee` is set
+#    after losing the 50/50.
+func get_pity_progress(banner_id: String) -> Dictionary:
+	return native.get_pity_progress(banner_id)
+
+
+# `{ "SSR": "5.00%", ... }` base rates for the rates disclosure, formatted with
+# the node's `rate_decimals` and `rate_rounding`.
+func get_rate_disclosure() -> Dictionary:
+	return native.get_rate_disclosure()
+
+
+# `resolver` receives the batch context Dictionary and returns a rarity or null.
+func set_rarity_resolver(resolver: FuncRef) -> void:
+	native.set_rarity_resolver(resolver)
+
+
+func request_reset_token() -> String:
+	return native.request_reset_token()
+
+
+# `keep` may contain "chances" and/or "pity". Everything else starts over,
+# including the scripted tutorial results, except the daily and monthly pull
+# counts behind the spending limits.
+func reset_profile(keep: Array, token: String) -> bool:
+	return native.reset_profile(keep, token)
+
+
+# One record per item of the last pull, empty unless `audit_rolls` is set or in debug builds:
+# `{ index, source, candidates: [[rarity, weight]], roll, item_index, rarity, item,
+#    pity_strategy, pity_state: { pity_count, hard_pity_count, pity, hard_pity }, pity_adjusted }`.
+func get_last_pull_audit() -> Array:
+	return native.get_last_pull_audit()
+
+
+# With `verifiable_pulls` set: SHA-256 of the next pull's seed, to show
+# before pulling. Null otherwise.
+func get_fairness_commitment():
+	return native.get_fairness_commitment()
+
+
+# `{ seed: String, commitment: String, unverified: Array }` of the last verifiable
+# pull, or null. `seed.sha256_text() == commitment` proves the seed was fixed
+# before the pull, except for the batch positions in `unverified` (scripted
+# results and rarities forced by a rarity resolver).
+func get_last_fairness_proof():
+	return native.get_last_fairness_proof()
+
+
+# Save chances, pity progress, guarantees and spend counters, e.g. to
+# "user://gacha.cfg". Pools and rates come from the game data and aren't saved,
+# and no pull history is kept to save. The node does this by itself with its
+# `save_path` on quit, when the app is paused and on leaving the tree.
+func save_state(path: String) -> bool:
+	return native.save_state(path)
+
+
+# Restore a `save_state` file. Keys missing from older saves keep their current
+# values, a missing file counts as nothing saved yet.
+func load_state(path: String) -> bool:
+	return native.load_state(path)
+
+
+# Record pulls from now on for a replayable bug report, see `stop_recording`.
+func start_recording() -> void:
+	native.start_recording()
+
+
+# Returns the recording Dictionary (seed, config hash, starting state and
+# commands) to attach to a bug report, or null if no recording was running.
+func stop_recording():
+	return native.stop_recording()
+
+
+# Debug builds only. Replays a recording on this node, overwriting its pity
+# state, and returns `{ config_matches, diverged_at, expected, actual }`.
+func play_recording(recording: Dictionary):
+	return native.play_recording(recording)
+
+
+# Describes the last `data`, `rarities` or `scripted_results` value that
+# failed to convert (offending key/index and expected type), or null.
+func last_conversion_error():
+	return native.last_conversion_error()
+
+
+# Native rarity values are externally tagged enums, e.g. `{ "SSR": {} }`.
+static func rarity_of(value: Dictionary) -> int:
+	for key in value:
+		var idx := _RARITY_NAMES.find(key)
+		if idx != -1:
+			return idx
+	push_error("unknown rarity value: %s" % [value])
+	return -1
+
+
+static func rarity_value(rarity: int) -> Dictionary:
+	return { _RARITY_NAMES[rarity]: {} }
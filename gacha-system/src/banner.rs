@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+
+use gdnative::prelude::*;
+
+use crate::{
+    error::{GachaError, Result},
+    gacha_core::{pool_problem, rates_from_array, to_array, ItemPools, Rarity},
+    profile::BannerCounters,
+    sampler::ItemSampler,
+};
+
+/// Id of the banner backed by the node's own `data` and `rarities`.
+pub const STANDARD_BANNER: &str = "standard";
+
+/// A banner besides the standard one, with its own pool, rates and pity progress.
+///
+/// Only pulled from by swapping it into the node, see `GachaSystem::with_banner`, so the
+/// pull logic only ever deals with the node's fields.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Banner {
+    pub data: ItemPools,
+    pub rarities: Vec<(Rarity, f64)>,
+    pub item_sampler: ItemSampler,
+    pub featured_items: Vec<String>,
+    pub featured_rate: f64,
+    pub pity_count: u32,
+    pub hard_pity_count: u32,
+    pub featured_guarantee: bool,
+}
+
+impl Banner {
+    fn to_dictionary(&self) -> Dictionary {
+        let dict = Dictionary::new();
+        dict.insert("data", self.data.to_variant());
+        dict.insert("rarities", to_array(&self.rarities));
+        dict.insert("featured", self.featured_items.clone());
+        dict.insert("featured_rate", self.featured_rate);
+        dict.into_shared()
+    }
+
+    pub fn counters(&self) -> BannerCounters {
+        BannerCounters {
+            pity_count: self.pity_count,
+            hard_pity_count: self.hard_pity_count,
+            featured_guarantee: self.featured_guarantee,
+        }
+    }
+
+    pub fn set_counters(&mut self, counters: BannerCounters) {
+        self.pity_count = counters.pity_count;
+        self.hard_pity_count = counters.hard_pity_count;
+        self.featured_guarantee = counters.featured_guarantee;
+    }
+}
+
+pub(crate) fn banners_to_dictionary(banners: &BTreeMap<String, Banner>) -> Dictionary {
+    banners
+        .iter()
+        .map(|(id, banner)| (id.as_str(), banner.to_dictionary()))
+        .collect::<Dictionary<Unique>>()
+        .into_shared()
+}
+
+/// Convert `{ id: { data, rarities, featured?, featured_rate? } }`, naming the offending entry
+/// on failure. `featured_rate` defaults to `0.5`.
+///
+/// Banners keep the pity progress they have in `current`, all get a fresh `sampler`.
+pub(crate) fn banners_from_dictionary(
+    dict: &Dictionary,
+    current: &BTreeMap<String, Banner>,
+    sampler: &ItemSampler,
+) -> Result<BTreeMap<String, Banner>> {
+    let mut banners = BTreeMap::new();
+    for (id, config) in dict.iter() {
+        let path = format!("banners[{id}]");
+        let id = String::from_variant(&id).map_err(|e| {
+            GachaError::InvalidProperty(path.clone(), format!("not a banner id: {e}"))
+        })?;
+        if id == STANDARD_BANNER {
+            return Err(GachaError::InvalidProperty(
+                path,
+                "the standard banner is configured by `data` and `rarities`".into(),
+            ));
+        }
+        let config = Dictionary::from_variant(&config).map_err(|e| {
+            GachaError::InvalidProperty(
+                path.clone(),
+                format!("expected a Dictionary with `data` and `rarities`: {e}"),
+            )
+        })?;
+        let field = |name: &str| {
+            config.get(name).ok_or_else(|| {
+                GachaError::InvalidProperty(format!("{path}.{name}"), "missing".into())
+            })
+        };
+        let data = Dictionary::from_variant(&field("data")?)
+            .map_err(|e| {
+                GachaError::InvalidProperty(
+                    format!("{path}.data"),
+                    format!("expected a Dictionary of item pools: {e}"),
+                )
+            })
+            .and_then(|data| ItemPools::from_dictionary(&data).map_err(|e| nested(e, &path)))?;
+        let rarities = VariantArray::from_variant(&field("rarities")?)
+            .map_err(|e| {
+                GachaError::InvalidProperty(
+                    format!("{path}.rarities"),
+                    format!("expected an Array of `[Rarity, rate]` pairs: {e}"),
+                )
+            })
+            .and_then(|rates| rates_from_array(&rates, &format!("{path}.rarities")))?;
+        if let Some(problem) = pool_problem(&data, &rarities) {
+            return Err(GachaError::InvalidProperty(path, problem));
+        }
+        let featured_items = match config.get("featured") {
+            Some(featured) => Vec::<String>::from_variant(&featured).map_err(|e| {
+                GachaError::InvalidProperty(
+                    format!("{path}.featured"),
+                    format!("expected an Array of item names: {e}"),
+                )
+            })?,
+            None => vec![],
+        };
+        let featured_rate = match config.get("featured_rate") {
+            Some(rate) => f64::from_variant(&rate)
+                .ok()
+                .filter(|rate| (0.0..=1.0).contains(rate))
+                .ok_or_else(|| {
+                    GachaError::InvalidProperty(
+                        format!("{path}.featured_rate"),
+                        format!("{rate} is not a number between 0 and 1"),
+                    )
+                })?,
+            None => 0.5,
+        };
+
+        let mut banner = Banner {
+            data,
+            rarities,
+            // box contents are indices into the pools, which may have changed
+            item_sampler: sampler.fresh(),
+            featured_items,
+            featured_rate,
+            ..Default::default()
+        };
+        if let Some(current) = current.get(&id) {
+            banner.set_counters(current.counters());
+        }
+        banners.insert(id, banner);
+    }
+    Ok(banners)
+}
+
+/// Report an error of a banner's field under the banner, e.g. `banners[limited].data[SSR]`.
+fn nested(e: GachaError, path: &str) -> GachaError {
+    match e {
+        GachaError::InvalidProperty(field, reason) => {
+            GachaError::InvalidProperty(format!("{path}.{field}"), reason)
+        }
+        e => e,
+    }
+}
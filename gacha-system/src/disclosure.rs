@@ -0,0 +1,177 @@
+use std::{fmt, str::FromStr};
+
+use crate::{
+    error::{GachaError, Result},
+    gacha_core::Rarity,
+};
+
+/// Most decimals a disclosed rate can have, rates are only kept to 1e-9 anyway.
+pub const MAX_RATE_DECIMALS: u32 = 6;
+
+/// How disclosed rates are rounded to `rate_decimals`, regulators don't agree on one way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum RateRounding {
+    /// Round half to even (banker's rounding).
+    #[default]
+    HalfEven,
+    /// Round half away from zero.
+    HalfUp,
+    /// Drop the digits past `rate_decimals`, never overstating a rate.
+    Truncate,
+}
+
+impl RateRounding {
+    pub const NAMES: [&'static str; 3] = ["half_even", "half_up", "truncate"];
+}
+
+impl fmt::Display for RateRounding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RateRounding::HalfEven => "half_even",
+            RateRounding::HalfUp => "half_up",
+            RateRounding::Truncate => "truncate",
+        })
+    }
+}
+
+impl FromStr for RateRounding {
+    type Err = GachaError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "half_even" => Ok(RateRounding::HalfEven),
+            "half_up" => Ok(RateRounding::HalfUp),
+            "truncate" => Ok(RateRounding::Truncate),
+            _ => Err(GachaError::InvalidProperty(
+                "rate_rounding".to_string(),
+                format!(
+                    "unknown rounding \"{s}\", expected one of {}",
+                    RateRounding::NAMES.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+/// Each rarity's share of the total weight as a percentage with exactly `decimals` decimals,
+/// e.g. `"0.600%"`.
+///
+/// Computed on the integer weights the rolls are made against, so ties are exact and a rate
+/// never rounds differently because of float noise.
+pub fn disclose_rates(
+    weights: &[(Rarity, u64)],
+    decimals: u32,
+    rounding: RateRounding,
+) -> Vec<(Rarity, String)> {
+    let decimals = decimals.min(MAX_RATE_DECIMALS);
+    let total: u128 = weights.iter().map(|(_, weight)| u128::from(*weight)).sum();
+    weights
+        .iter()
+        .map(|(rarity, weight)| {
+            let scaled = if total == 0 {
+                0
+            } else {
+                round_div(
+                    u128::from(*weight) * 100 * 10_u128.pow(decimals),
+                    total,
+                    rounding,
+                )
+            };
+            (*rarity, format_percent(scaled, decimals))
+        })
+        .collect()
+}
+
+fn round_div(numerator: u128, denominator: u128, rounding: RateRounding) -> u128 {
+    let (quotient, remainder) = (numerator / denominator, numerator % denominator);
+    let round_up = match rounding {
+        RateRounding::Truncate => false,
+        RateRounding::HalfUp => remainder * 2 >= denominator,
+        RateRounding::HalfEven => {
+            remainder * 2 > denominator || (remainder * 2 == denominator && quotient % 2 == 1)
+        }
+    };
+    quotient + u128::from(round_up)
+}
+
+/// `scaled` hundredths-of-a-percent (for 2 `decimals`) as `"12.34%"`.
+fn format_percent(scaled: u128, decimals: u32) -> String {
+    let scale = 10_u128.pow(decimals);
+    if decimals == 0 {
+        return format!("{scaled}%");
+    }
+    let decimals = decimals as usize;
+    format!("{}.{:0decimals$}%", scaled / scale, scaled % scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{disclose_rates, RateRounding};
+    use crate::gacha_core::Rarity;
+
+    fn disclose(weights: &[u64], decimals: u32, rounding: RateRounding) -> Vec<String> {
+        let weights: Vec<(Rarity, u64)> = weights.iter().map(|w| (Rarity::N, *w)).collect();
+        disclose_rates(&weights, decimals, rounding)
+            .into_iter()
+            .map(|(_, rate)| rate)
+            .collect()
+    }
+
+    #[test]
+    fn exact_strings() {
+        let weights = [6, 994];
+        assert_eq!(
+            disclose(&weights, 1, RateRounding::HalfEven),
+            ["0.6%", "99.4%"]
+        );
+        assert_eq!(
+            disclose(&weights, 3, RateRounding::HalfEven),
+            ["0.600%", "99.400%"]
+        );
+        assert_eq!(disclose(&weights, 0, RateRounding::HalfUp), ["1%", "99%"]);
+        assert_eq!(disclose(&weights, 0, RateRounding::Truncate), ["0%", "99%"]);
+        assert_eq!(
+            disclose(&[1, 0], 2, RateRounding::HalfEven),
+            ["100.00%", "0.00%"]
+        );
+        assert_eq!(
+            disclose(&[0, 0], 1, RateRounding::HalfEven),
+            ["0.0%", "0.0%"]
+        );
+    }
+
+    #[test]
+    fn ties() {
+        // 0.125% and 0.135%, exactly halfway at 2 decimals
+        let weights = [125, 135, 99_740];
+        assert_eq!(
+            disclose(&weights, 2, RateRounding::HalfEven),
+            ["0.12%", "0.14%", "99.74%"]
+        );
+        assert_eq!(
+            disclose(&weights, 2, RateRounding::HalfUp),
+            ["0.13%", "0.14%", "99.74%"]
+        );
+        assert_eq!(
+            disclose(&weights, 2, RateRounding::Truncate),
+            ["0.12%", "0.13%", "99.74%"]
+        );
+        // 1/3 doesn't tie, every rounding but truncation goes the same way
+        assert_eq!(
+            disclose(&[2, 1], 1, RateRounding::HalfEven),
+            ["66.7%", "33.3%"]
+        );
+        assert_eq!(
+            disclose(&[2, 1], 1, RateRounding::Truncate),
+            ["66.6%", "33.3%"]
+        );
+    }
+
+    #[test]
+    fn rounding_names() {
+        for name in RateRounding::NAMES {
+            assert_eq!(name.parse::<RateRounding>().unwrap().to_string(), name);
+        }
+        assert!("bankers".parse::<RateRounding>().is_err());
+    }
+}
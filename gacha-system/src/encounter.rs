@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    error::{GachaError, Result},
+    rng::{self, RngStreams},
+    sampler::pick_weighted,
+};
+use gdnative::prelude::*;
+
+/// Enemies fought together, picked proportionally to `weight` among the stage's groups.
+#[derive(Debug, FromVariant, Clone, PartialEq)]
+struct EncounterGroup {
+    enemies: Vec<String>,
+    weight: f64,
+}
+
+/// Weighted enemy groups per stage, rolled into encounter compositions.
+///
+/// Tables come from stage data files as `{ stage: [{ enemies: [String], weight: float }] }`,
+/// see `set_tables`. Rolls are derived from the seed set with `set_seed`.
+#[derive(NativeClass)]
+#[inherit(Reference)]
+pub struct EncounterTables {
+    tables: BTreeMap<String, Vec<EncounterGroup>>,
+    streams: RngStreams,
+}
+
+#[methods]
+impl EncounterTables {
+    fn new(_owner: &Reference) -> Self {
+        EncounterTables {
+            tables: BTreeMap::new(),
+            streams: RngStreams::from_entropy(),
+        }
+    }
+
+    #[method]
+    fn set_seed(&mut self, seed: i64) {
+        self.streams = RngStreams::new(seed as u64);
+    }
+
+    /// Replace the tables, e.g. with `parse_json` of a stage data file. Returns `false` and keeps
+    /// the current tables if an entry is invalid.
+    #[method]
+    fn set_tables(&mut self, tables: Dictionary) -> bool {
+        match tables_from_dictionary(&tables) {
+            Ok(tables) => {
+                self.tables = tables;
+                true
+            }
+            Err(e) => {
+                godot_error!("{e}");
+                false
+            }
+        }
+    }
+
+    #[method]
+    fn get_stages(&self) -> Vec<String> {
+        self.tables.keys().cloned().collect()
+    }
+
+    /// Enemies of a random group of `stage`, empty if the stage has no table.
+    #[method]
+    fn roll(&mut self, stage: String) -> Vec<String> {
+        let Some(groups) = self.tables.get(&stage) else {
+            godot_error!("no encounter table for stage {stage:?}");
+            return vec![];
+        };
+        let weights = groups.iter().map(|group| group.weight);
+        // validated in `set_tables`, at least one group has a positive weight
+        let idx = pick_weighted(weights, self.streams.stream(rng::ENCOUNTERS))
+            .expect("encounter weights are validated");
+        groups[idx].enemies.clone()
+    }
+}
+
+fn tables_from_dictionary(dict: &Dictionary) -> Result<BTreeMap<String, Vec<EncounterGroup>>> {
+    let mut tables = BTreeMap::new();
+    for (stage, groups) in dict.iter() {
+        let path = format!("tables[{stage}]");
+        let stage = String::from_variant(&stage).map_err(|e| {
+            GachaError::InvalidProperty(path.clone(), format!("not a stage name: {e}"))
+        })?;
+        let groups = <Vec<EncounterGroup>>::from_variant(&groups).map_err(|e| {
+            GachaError::InvalidProperty(
+                path.clone(),
+                format!("expected an Array of `enemies`/`weight` Dictionaries: {e}"),
+            )
+        })?;
+        validate_groups(&groups, &path)?;
+        tables.insert(stage, groups);
+    }
+    Ok(tables)
+}
+
+fn validate_groups(groups: &[EncounterGroup], path: &str) -> Result<()> {
+    for (idx, group) in groups.iter().enumerate() {
+        if group.enemies.is_empty() {
+            return Err(GachaError::InvalidProperty(
+                format!("{path}[{idx}].enemies"),
+                "a group needs at least one enemy".into(),
+            ));
+        }
+        if !group.weight.is_finite() || group.weight < 0.0 {
+            return Err(GachaError::InvalidProperty(
+                format!("{path}[{idx}].weight"),
+                format!("{} is not a finite, non-negative number", group.weight),
+            ));
+        }
+    }
+    if !groups.iter().any(|group| group.weight > 0.0) {
+        return Err(GachaError::InvalidProperty(
+            path.into(),
+            "no group with a positive weight".into(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_groups, EncounterGroup, EncounterTables};
+    use crate::rng::RngStreams;
+    use std::collections::BTreeMap;
+
+    fn group(enemies: &[&str], weight: f64) -> EncounterGroup {
+        EncounterGroup {
+            enemies: enemies.iter().map(ToString::to_string).collect(),
+            weight,
+        }
+    }
+
+    #[test]
+    fn validation() {
+        assert!(validate_groups(&[group(&["slime"], 1.0), group(&["bat"], 0.0)], "t").is_ok());
+        assert!(validate_groups(&[group(&[], 1.0)], "t").is_err());
+        assert!(validate_groups(&[group(&["slime"], -1.0)], "t").is_err());
+        assert!(validate_groups(&[group(&["slime"], 0.0)], "t").is_err());
+        assert!(validate_groups(&[], "t").is_err());
+    }
+
+    #[test]
+    fn rolls() {
+        let tables = BTreeMap::from([(
+            "1-1".to_string(),
+            vec![group(&["slime", "slime"], 3.0), group(&["bat"], 0.0)],
+        )]);
+        let mut encounters = EncounterTables {
+            tables,
+            streams: RngStreams::new(469),
+        };
+        for _ in 0..20 {
+            assert_eq!(encounters.roll("1-1".into()), vec!["slime", "slime"]);
+        }
+        assert!(encounters.roll("9-9".into()).is_empty());
+    }
+}
@@ -1,6 +1,8 @@
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 
+use crate::{gacha_core::MIN_RATE, state::STATE_VERSION};
+
 pub(crate) type Result<T> = std::result::Result<T, GachaError>;
 
 #[derive(Debug)]
@@ -8,6 +10,11 @@ pub(crate) type Result<T> = std::result::Result<T, GachaError>;
 pub(crate) enum GachaError {
     InvalidRarity(String),
     RarityWithNoData(String),
+    InvalidRate(String, f64),
+    NoRarityAvailable,
+    InvalidProperty(String, String),
+    SpendingLimit(String, u32, u32),
+    UnsupportedSaveVersion(u32),
 }
 
 impl Display for GachaError {
@@ -16,6 +23,17 @@ impl Display for GachaError {
         let msg = match self {
             InvalidRarity(rty) => format!("\"{rty}\" is not a valid rarity in gacha pool"),
             RarityWithNoData(rty) => format!("gacha pool for rarity \"{rty}\" has no data"),
+            InvalidRate(rty, rate) => {
+                format!("rate {rate} of rarity \"{rty}\" is neither 0 nor a finite number of at least {MIN_RATE:e}")
+            }
+            NoRarityAvailable => "no rarity with a positive rate is available".to_string(),
+            InvalidProperty(path, reason) => format!("invalid value for `{path}`: {reason}"),
+            SpendingLimit(period, limit, allowed) => {
+                format!("{period} limit of {limit} pulls reached, {allowed} more allowed")
+            }
+            UnsupportedSaveVersion(version) => {
+                format!("save is version {version}, this build only reads up to {STATE_VERSION}")
+            }
         };
         f.write_str(&msg)
     }
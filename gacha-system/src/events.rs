@@ -0,0 +1,290 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use gdnative::prelude::*;
+use rand::Rng;
+
+use crate::{
+    error::{GachaError, Result},
+    rng::{self, RngStreams},
+    sampler::pick_weighted,
+};
+
+/// Table whose events can happen at every location, layered under the location's own table.
+const ANYWHERE: &str = "*";
+
+/// An event that can be rolled at a location.
+#[derive(Debug, FromVariant, Clone, PartialEq)]
+struct EventEntry {
+    id: String,
+    weight: f64,
+    /// Rolls to wait before the event can happen again, `0` when unset.
+    cooldown: Option<u32>,
+    /// Only ever happen once, `false` when unset.
+    once: Option<bool>,
+    /// Flags that all have to be set for the event to happen.
+    requires: Option<Vec<String>>,
+}
+
+/// Overworld random events rolled from per-location tables.
+///
+/// Tables are `{ location: [{ id, weight, cooldown?, once?, requires? }] }`, the events of the
+/// `"*"` table can happen anywhere. Rolls are derived from the seed set with `set_seed`, so a
+/// seeded run can be replayed in tests.
+#[derive(NativeClass)]
+#[inherit(Reference)]
+pub struct RandomEvents {
+    /// Chance of any event happening on a roll, when one is eligible. Clamped to `0..=1`,
+    /// NaN and infinities count as `0`.
+    #[property]
+    event_chance: f64,
+    tables: BTreeMap<String, Vec<EventEntry>>,
+    streams: RngStreams,
+    /// Rolls made so far, the clock cooldowns are counted in.
+    rolls: u64,
+    /// Roll at which each event last happened.
+    last_seen: HashMap<String, u64>,
+    /// `once` events that already happened.
+    fired: BTreeSet<String>,
+}
+
+#[methods]
+impl RandomEvents {
+    fn new(_owner: &Reference) -> Self {
+        Self::with_streams(RngStreams::from_entropy())
+    }
+
+    fn with_streams(streams: RngStreams) -> Self {
+        RandomEvents {
+            event_chance: 1.0,
+            tables: BTreeMap::new(),
+            streams,
+            rolls: 0,
+            last_seen: HashMap::new(),
+            fired: BTreeSet::new(),
+        }
+    }
+
+    /// Restart rolling from `seed`, forgetting cooldowns but not the `once` events that happened.
+    #[method]
+    fn set_seed(&mut self, seed: i64) {
+        self.streams = RngStreams::new(seed as u64);
+        self.rolls = 0;
+        self.last_seen.clear();
+    }
+
+    /// Replace the tables. Returns `false` and keeps the current tables if an entry is invalid.
+    #[method]
+    fn set_tables(&mut self, tables: Dictionary) -> bool {
+        match tables_from_dictionary(&tables) {
+            Ok(tables) => {
+                self.tables = tables;
+                true
+            }
+            Err(e) => {
+                godot_error!("{e}");
+                false
+            }
+        }
+    }
+
+    /// Id of the event happening at `location`, or null. `flags` are the keys of the flags
+    /// currently set, checked against each event's `requires`.
+    #[method]
+    fn roll(&mut self, location: String, flags: Vec<String>) -> Option<String> {
+        self.rolls += 1;
+        let candidates = self.candidates(&location, &flags);
+        // weights are validated in `set_tables`, so only a positive one is needed to pick
+        if !candidates.iter().any(|event| event.weight > 0.0) {
+            return None;
+        }
+
+        let rng = self.streams.stream(rng::EVENTS);
+        // `clamp` keeps NaN, which `gen_bool` panics on
+        let chance = if self.event_chance.is_finite() {
+            self.event_chance.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        if !rng.gen_bool(chance) {
+            return None;
+        }
+        let idx = pick_weighted(candidates.iter().map(|event| event.weight), rng)?;
+        let event = candidates[idx].clone();
+        self.last_seen.insert(event.id.clone(), self.rolls);
+        if event.once == Some(true) {
+            self.fired.insert(event.id.clone());
+        }
+        Some(event.id)
+    }
+
+    /// Ids of the `once` events that already happened, to keep with the save.
+    #[method]
+    fn get_fired(&self) -> Vec<String> {
+        self.fired.iter().cloned().collect()
+    }
+
+    #[method]
+    fn set_fired(&mut self, fired: Vec<String>) {
+        self.fired = fired.into_iter().collect();
+    }
+
+    /// Events that can happen at `location` right now, from the `"*"` table and the location's.
+    fn candidates(&self, location: &str, flags: &[String]) -> Vec<EventEntry> {
+        let mut layers = vec![ANYWHERE];
+        // rolling at `"*"` itself mustn't layer that table twice and double its weights
+        if location != ANYWHERE {
+            layers.push(location);
+        }
+        layers
+            .into_iter()
+            .filter_map(|layer| self.tables.get(layer))
+            .flatten()
+            .filter(|event| self.eligible(event, flags))
+            .cloned()
+            .collect()
+    }
+
+    fn eligible(&self, event: &EventEntry, flags: &[String]) -> bool {
+        let cooled_down = self
+            .last_seen
+            .get(&event.id)
+            .is_none_or(|&seen| self.rolls - seen > u64::from(event.cooldown.unwrap_or(0)));
+        let new = event.once != Some(true) || !self.fired.contains(&event.id);
+        let unlocked = event
+            .requires
+            .iter()
+            .flatten()
+            .all(|flag| flags.contains(flag));
+        cooled_down && new && unlocked
+    }
+}
+
+fn tables_from_dictionary(dict: &Dictionary) -> Result<BTreeMap<String, Vec<EventEntry>>> {
+    let mut tables = BTreeMap::new();
+    for (location, events) in dict.iter() {
+        let path = format!("tables[{location}]");
+        let location = String::from_variant(&location).map_err(|e| {
+            GachaError::InvalidProperty(path.clone(), format!("not a location name: {e}"))
+        })?;
+        let events = <Vec<EventEntry>>::from_variant(&events).map_err(|e| {
+            GachaError::InvalidProperty(
+                path.clone(),
+                format!("expected an Array of event Dictionaries with `id` and `weight`: {e}"),
+            )
+        })?;
+        for (idx, event) in events.iter().enumerate() {
+            if !event.weight.is_finite() || event.weight < 0.0 {
+                return Err(GachaError::InvalidProperty(
+                    format!("{path}[{idx}].weight"),
+                    format!("{} is not a finite, non-negative number", event.weight),
+                ));
+            }
+        }
+        tables.insert(location, events);
+    }
+    Ok(tables)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventEntry, RandomEvents};
+    use crate::rng::RngStreams;
+    use std::collections::BTreeMap;
+
+    fn event(id: &str, cooldown: u32, once: bool, requires: &[&str]) -> EventEntry {
+        EventEntry {
+            id: id.into(),
+            weight: 1.0,
+            cooldown: Some(cooldown),
+            once: Some(once),
+            requires: Some(requires.iter().map(ToString::to_string).collect()),
+        }
+    }
+
+    fn events(tables: Vec<(&str, Vec<EventEntry>)>) -> RandomEvents {
+        let mut events = RandomEvents::with_streams(RngStreams::new(474));
+        events.tables = tables
+            .into_iter()
+            .map(|(location, entries)| (location.to_string(), entries))
+            .collect::<BTreeMap<_, _>>();
+        events
+    }
+
+    fn roll(events: &mut RandomEvents, location: &str, flags: &[&str]) -> Option<String> {
+        let flags = flags.iter().map(ToString::to_string).collect();
+        events.roll(location.into(), flags)
+    }
+
+    #[test]
+    fn cooldowns_and_once() {
+        let mut events = events(vec![(
+            "forest",
+            vec![
+                event("wolves", 2, false, &[]),
+                event("hermit", 0, true, &[]),
+            ],
+        )]);
+        let rolled: Vec<_> = (0..12).map(|_| roll(&mut events, "forest", &[])).collect();
+
+        let hermit: Vec<_> = rolled
+            .iter()
+            .filter(|id| id.as_deref() == Some("hermit"))
+            .collect();
+        assert_eq!(hermit.len(), 1);
+        assert_eq!(events.get_fired(), vec!["hermit"]);
+        for (idx, id) in rolled.iter().enumerate() {
+            if id.as_deref() == Some("wolves") {
+                // two rolls of cooldown after each pack of wolves
+                for next in rolled.iter().skip(idx + 1).take(2) {
+                    assert_ne!(next.as_deref(), Some("wolves"));
+                }
+            }
+        }
+        assert!(rolled.iter().any(Option::is_none));
+    }
+
+    #[test]
+    fn unusable_event_chance() {
+        let mut events = events(vec![("forest", vec![event("wolves", 0, false, &[])])]);
+        events.event_chance = f64::NAN;
+        assert_eq!(roll(&mut events, "forest", &[]), None);
+        events.event_chance = f64::INFINITY;
+        assert_eq!(roll(&mut events, "forest", &[]), None);
+        events.event_chance = 2.0;
+        assert_eq!(roll(&mut events, "forest", &[]).as_deref(), Some("wolves"));
+    }
+
+    #[test]
+    fn layers_and_flags() {
+        let mut events = events(vec![
+            ("*", vec![event("merchant", 0, false, &["met_merchant"])]),
+            ("cave", vec![event("bats", 0, false, &[])]),
+        ]);
+        for _ in 0..10 {
+            assert_eq!(roll(&mut events, "cave", &[]).as_deref(), Some("bats"));
+            assert_eq!(roll(&mut events, "town", &[]), None);
+        }
+        let rolled: Vec<_> = (0..20)
+            .filter_map(|_| roll(&mut events, "cave", &["met_merchant"]))
+            .collect();
+        assert!(rolled.contains(&"merchant".to_string()));
+        assert!(rolled.contains(&"bats".to_string()));
+    }
+
+    #[test]
+    fn wildcard_layered_once() {
+        let events = events(vec![
+            ("*", vec![event("merchant", 0, false, &[])]),
+            ("cave", vec![event("bats", 0, false, &[])]),
+        ]);
+        let ids = |location| -> Vec<String> {
+            events
+                .candidates(location, &[])
+                .into_iter()
+                .map(|event| event.id)
+                .collect()
+        };
+        assert_eq!(ids("*"), ["merchant"]);
+        assert_eq!(ids("cave"), ["merchant", "bats"]);
+    }
+}
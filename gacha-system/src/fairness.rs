@@ -0,0 +1,71 @@
+use gdnative::prelude::*;
+use sha2::{Digest, Sha256};
+
+/// Seed of one verifiable pull, revealed after the pull alongside the commitment published before it.
+///
+/// Anyone can check that `commitment` is the SHA-256 of `seed` (e.g. `seed.sha256_text()` in
+/// GDScript), so the seed, and with it the pull results, couldn't be changed after the fact.
+#[derive(Debug, Clone, PartialEq, ToVariant)]
+pub struct FairnessProof {
+    /// Seed of the pull's RNG as 16 hex digits.
+    pub seed: String,
+    /// Hex SHA-256 of `seed`.
+    pub commitment: String,
+    /// Positions in the batch of the pulls the seed didn't decide: scripted results and
+    /// rarities forced by a rarity resolver. The proof only covers the other pulls.
+    pub unverified: Vec<u32>,
+}
+
+impl FairnessProof {
+    pub fn new(seed: u64) -> Self {
+        let seed = format!("{seed:016x}");
+        FairnessProof {
+            commitment: commitment(&seed),
+            seed,
+            unverified: vec![],
+        }
+    }
+}
+
+/// Hex SHA-256 of the `seed` text, what gets published before a pull.
+pub fn commitment(seed: &str) -> String {
+    sha256_hex(seed)
+}
+
+/// Hex SHA-256 of `text`, the same on every build unlike `std`'s hashers.
+pub fn sha256_hex(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{commitment, FairnessProof};
+
+    #[test]
+    fn sha256_vectors() {
+        assert_eq!(
+            commitment(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            commitment("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // two blocks
+        assert_eq!(
+            commitment("abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+
+    #[test]
+    fn proof() {
+        let proof = FairnessProof::new(477);
+        assert_eq!(proof.seed, "00000000000001dd");
+        assert_eq!(proof.commitment, commitment(&proof.seed));
+        assert_ne!(proof.commitment, FairnessProof::new(478).commitment);
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::BTreeMap;
+
+use gdnative::api::ConfigFile;
+use gdnative::core_types::{GodotError, VariantDispatch};
+use gdnative::prelude::*;
+
+/// Namespace of keys that don't name one, e.g. `"met_king"`.
+const GLOBAL: &str = "global";
+
+/// Value of a flag, the only types that can be stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FlagValue {
+    Bool(bool),
+    Int(i64),
+    Str(String),
+}
+
+impl FlagValue {
+    fn from_variant(value: &Variant) -> Option<Self> {
+        match value.dispatch() {
+            VariantDispatch::Bool(value) => Some(FlagValue::Bool(value)),
+            VariantDispatch::I64(value) => Some(FlagValue::Int(value)),
+            VariantDispatch::GodotString(value) => Some(FlagValue::Str(value.to_string())),
+            _ => None,
+        }
+    }
+}
+
+impl ToVariant for FlagValue {
+    fn to_variant(&self) -> Variant {
+        match self {
+            FlagValue::Bool(value) => value.to_variant(),
+            FlagValue::Int(value) => value.to_variant(),
+            FlagValue::Str(value) => value.to_variant(),
+        }
+    }
+}
+
+/// Split `"namespace/name"` at the first `/`, keys without one belong to [`GLOBAL`].
+fn split_key(key: &str) -> Option<(&str, &str)> {
+    let (namespace, name) = key.split_once('/').unwrap_or((GLOBAL, key));
+    (!namespace.is_empty() && !name.is_empty()).then_some((namespace, name))
+}
+
+/// World state shared by dialogue conditions, quests and tutorials, meant to be an autoload.
+///
+/// Flags are bools, ints or strings addressed as `"namespace/name"` (e.g. `"quest/found_key"`),
+/// keys without a namespace go to `"global"`. Every change emits `flag_changed`. Flags are
+/// loaded from `save_path` on `_ready` and saved back on `_exit_tree` or `save_flags`.
+#[derive(NativeClass, Debug, Default)]
+#[inherit(Node)]
+#[register_with(Self::register)]
+pub struct GameFlags {
+    /// ConfigFile the flags are kept in, one section per namespace.
+    #[property]
+    save_path: String,
+    flags: BTreeMap<String, BTreeMap<String, FlagValue>>,
+}
+
+#[methods]
+impl GameFlags {
+    fn new(_owner: &Node) -> Self {
+        GameFlags {
+            save_path: "user://game_flags.cfg".into(),
+            ..Default::default()
+        }
+    }
+
+    fn register(builder: &ClassBuilder<Self>) {
+        builder
+            .signal("flag_changed")
+            .with_param("key", VariantType::GodotString)
+            .with_param("value", VariantType::Nil)
+            .done();
+    }
+
+    #[method]
+    fn _ready(&mut self) {
+        self.load_flags();
+    }
+
+    #[method]
+    fn _exit_tree(&self) {
+        self.save_flags();
+    }
+
+    /// Also save when the window is closed or the app goes to the background, mobile systems
+    /// kill paused apps without `_exit_tree` ever running.
+    #[method]
+    fn _notification(&self, what: i64) {
+        if what == Node::NOTIFICATION_WM_QUIT_REQUEST || what == Node::NOTIFICATION_APP_PAUSED {
+            self.save_flags();
+        }
+    }
+
+    /// Set `key` to a bool, int or string. Returns `false` if the key or the value type is invalid.
+    #[method]
+    fn set_flag(&mut self, #[base] owner: &Node, key: String, value: Variant) -> bool {
+        let Some(flag) = FlagValue::from_variant(&value) else {
+            godot_error!("flag {key:?} can't hold {value}, only bools, ints and strings");
+            return false;
+        };
+        match self.set(&key, flag) {
+            Some(changed) => {
+                if changed {
+                    owner.emit_signal("flag_changed", &[key.to_variant(), value]);
+                }
+                true
+            }
+            None => {
+                godot_error!("invalid flag key {key:?}, expected \"namespace/name\" or \"name\"");
+                false
+            }
+        }
+    }
+
+    /// Value of `key`, or `default` if it isn't set.
+    #[method]
+    fn get_flag(&self, key: String, #[opt] default: Variant) -> Variant {
+        self.get(&key).map_or(default, ToVariant::to_variant)
+    }
+
+    #[method]
+    fn has_flag(&self, key: String) -> bool {
+        self.get(&key).is_some()
+    }
+
+    /// Remove `key`, emitting `flag_changed` with a null value if it was set.
+    #[method]
+    fn erase_flag(&mut self, #[base] owner: &Node, key: String) -> bool {
+        let erased = self.erase(&key).is_some();
+        if erased {
+            owner.emit_signal("flag_changed", &[key.to_variant(), Variant::nil()]);
+        }
+        erased
+    }
+
+    /// All flags of `namespace` as `{ name: value }`.
+    #[method]
+    fn get_namespace(&self, namespace: String) -> Dictionary {
+        let flags = Dictionary::new();
+        for (name, value) in self.flags.get(&namespace).into_iter().flatten() {
+            flags.insert(name, value);
+        }
+        flags.into_shared()
+    }
+
+    /// Remove every flag of `namespace`, e.g. when a quest line restarts.
+    #[method]
+    fn clear_namespace(&mut self, #[base] owner: &Node, namespace: String) {
+        let cleared = self.flags.remove(&namespace).unwrap_or_default();
+        for name in cleared.keys() {
+            let key = format!("{namespace}/{name}");
+            owner.emit_signal("flag_changed", &[key.to_variant(), Variant::nil()]);
+        }
+    }
+
+    /// Write all flags to `save_path`.
+    #[method]
+    fn save_flags(&self) -> bool {
+        let file = ConfigFile::new();
+        for (namespace, flags) in &self.flags {
+            for (name, value) in flags {
+                file.set_value(namespace.as_str(), name.as_str(), value.to_variant());
+            }
+        }
+        match file.save(self.save_path.as_str()) {
+            Ok(()) => true,
+            Err(e) => {
+                godot_error!("could not save flags to {}: {e}", self.save_path);
+                false
+            }
+        }
+    }
+
+    /// Replace all flags with the ones saved in `save_path`, without emitting `flag_changed`.
+    /// A missing file counts as no flags set.
+    #[method]
+    fn load_flags(&mut self) -> bool {
+        let file = ConfigFile::new();
+        match file.load(self.save_path.as_str()) {
+            Ok(()) => {}
+            Err(GodotError::FileNotFound) => {
+                self.flags.clear();
+                return true;
+            }
+            Err(e) => {
+                godot_error!("could not load flags from {}: {e}", self.save_path);
+                return false;
+            }
+        }
+
+        self.flags.clear();
+        for namespace in file.get_sections().to_vec() {
+            for name in file.get_section_keys(namespace.clone()).to_vec() {
+                let value = file.get_value(namespace.clone(), name.clone(), Variant::nil());
+                match FlagValue::from_variant(&value) {
+                    Some(value) => {
+                        self.flags
+                            .entry(namespace.to_string())
+                            .or_default()
+                            .insert(name.to_string(), value);
+                    }
+                    None => godot_warn!("skipped saved flag {namespace}/{name}: {value}"),
+                }
+            }
+        }
+        true
+    }
+
+    /// Store `value` under `key`. Returns whether it changed, or `None` for an invalid key.
+    fn set(&mut self, key: &str, value: FlagValue) -> Option<bool> {
+        let (namespace, name) = split_key(key)?;
+        let previous = self
+            .flags
+            .entry(namespace.to_owned())
+            .or_default()
+            .insert(name.to_owned(), value.clone());
+        Some(previous != Some(value))
+    }
+
+    fn get(&self, key: &str) -> Option<&FlagValue> {
+        let (namespace, name) = split_key(key)?;
+        self.flags.get(namespace)?.get(name)
+    }
+
+    fn erase(&mut self, key: &str) -> Option<FlagValue> {
+        let (namespace, name) = split_key(key)?;
+        let flags = self.flags.get_mut(namespace)?;
+        let erased = flags.remove(name);
+        if flags.is_empty() {
+            self.flags.remove(namespace);
+        }
+        erased
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FlagValue, GameFlags};
+
+    #[test]
+    fn namespaces() {
+        let mut flags = GameFlags::default();
+        assert_eq!(flags.set("met_king", FlagValue::Bool(true)), Some(true));
+        assert_eq!(flags.set("quest/stage", FlagValue::Int(2)), Some(true));
+        assert_eq!(
+            flags.set("quest/npc/name", FlagValue::Str("Ada".into())),
+            Some(true)
+        );
+
+        assert_eq!(flags.get("global/met_king"), Some(&FlagValue::Bool(true)));
+        assert_eq!(flags.get("quest/stage"), Some(&FlagValue::Int(2)));
+        assert_eq!(
+            flags.flags["quest"]["npc/name"],
+            FlagValue::Str("Ada".into())
+        );
+        assert_eq!(flags.get("stage"), None);
+
+        assert_eq!(flags.set("/stage", FlagValue::Int(1)), None);
+        assert_eq!(flags.set("quest/", FlagValue::Int(1)), None);
+        assert_eq!(flags.set("", FlagValue::Int(1)), None);
+    }
+
+    #[test]
+    fn changes() {
+        let mut flags = GameFlags::default();
+        assert_eq!(flags.set("quest/stage", FlagValue::Int(1)), Some(true));
+        assert_eq!(flags.set("quest/stage", FlagValue::Int(1)), Some(false));
+        // same number, different type
+        assert_eq!(
+            flags.set("quest/stage", FlagValue::Str("1".into())),
+            Some(true)
+        );
+
+        assert_eq!(flags.erase("quest/stage"), Some(FlagValue::Str("1".into())));
+        assert_eq!(flags.erase("quest/stage"), None);
+        assert!(flags.flags.is_empty());
+    }
+}
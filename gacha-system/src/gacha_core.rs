@@ -1,8 +1,56 @@
-use gdnative::{export::Export, prelude::*};
-use rand::{rngs::ThreadRng, seq::SliceRandom, thread_rng, Rng};
-use std::{collections::HashMap, ops::Range};
+use gdnative::{
+    api::{ConfigFile, FuncRef, OS},
+    core_types::GodotError,
+    export::{
+        hint::{EnumHint, StringHint},
+        Export,
+    },
+    prelude::*,
+};
+use rand::{thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::{
+    collections::BTreeMap,
+    mem,
+    ops::Deref,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use crate::error::{GachaError, Result};
+use crate::{
+    banner::{banners_from_dictionary, banners_to_dictionary, Banner, STANDARD_BANNER},
+    disclosure::{disclose_rates, RateRounding},
+    error::{GachaError, Result},
+    fairness::{self, FairnessProof},
+    limits::SpendTracker,
+    pity::{apply_modifiers, PityCounters, PityKind, PityStrategy, SoftRamp},
+    profile::{shared_counters, ProfileCounters, SharedCounters},
+    recording::{BannerState, RecordedCommand, Recording, ReplayReport, RECORDING_VERSION},
+    reveal::{plan_reveal, RevealPlan},
+    rng::{self, RngStreams},
+    sampler::ItemSampler,
+    state::{SavedBanner, SavedState},
+};
+
+/// Version of the GDScript-facing API as `major.minor`, checked by the facade in
+/// `frontend/lib/gacha_system.gd` at startup.
+///
+/// Bump the major version for changes that break existing scripts (renamed or removed
+/// methods, signals or properties, changed signatures), the minor version for additions.
+pub const API_VERSION: &str = "2.6";
+
+/// Version of the `config_hash` input format, bump it when the format or the hashed settings
+/// change.
+const CONFIG_HASH_VERSION: u32 = 1;
+/// State `reset_profile` can be asked to keep.
+const RESET_KEEP_KEYS: [&str; 2] = ["chances", "pity"];
+/// Rates are turned into integer weights with this resolution before sampling.
+const RATE_SCALE: f64 = 1e9;
+/// Smallest positive rate, finer ones would be lost when turned into integer weights.
+pub(crate) const MIN_RATE: f64 = 1.0 / RATE_SCALE;
+/// `verbosity` level that prints one summary line per `pull` call.
+const VERBOSITY_SUMMARY: u32 = 1;
+/// `verbosity` level that additionally prints individual rolls.
+const VERBOSITY_ROLLS: u32 = 2;
 
 #[allow(clippy::upper_case_acronyms)]
 #[derive(ToVariant, FromVariant, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -15,10 +63,12 @@ pub enum Rarity {
 
 impl ToVariantEq for Rarity {}
 
-#[derive(Debug, ToVariant, FromVariant, Clone)]
+#[derive(Debug, ToVariant, FromVariant, Clone, PartialEq)]
 pub struct GachaItem {
     pub name: String,
     pub rarity: Rarity,
+    /// Relative chance within its rarity for the `weighted` item sampler, `1.0` when unset.
+    pub weight: Option<f64>,
 }
 
 impl Export for GachaItem {
@@ -28,8 +78,130 @@ impl Export for GachaItem {
     }
 }
 
+/// Record of how a single pull was decided, for verifying disputed pulls after the fact.
+#[derive(Debug, ToVariant, Clone)]
+pub struct RollAudit {
+    /// Position of the pull within its batch.
+    pub index: u32,
+    /// How the rarity was decided: `"scripted"`, `"resolver"` or `"roll"`.
+    pub source: String,
+    /// Integer rarity weights the roll was made against, empty unless rolled.
+    pub candidates: Vec<(Rarity, u64)>,
+    /// Raw rarity roll within the total of `candidates`.
+    pub roll: Option<u64>,
+    /// Index of the pulled item within its rarity pool, `None` for scripted results.
+    pub item_index: Option<usize>,
+    pub rarity: Rarity,
+    pub item: String,
+    /// Pity strategy in effect, as named by the `pity_strategy` property.
+    pub pity_strategy: String,
+    /// Pity counters and thresholds right before this pull.
+    pub pity_state: PityCounters,
+    /// Whether pity changed the rates `candidates` were made from.
+    pub pity_adjusted: bool,
+}
+
+impl RollAudit {
+    fn new(index: u32, source: &str, item: &GachaItem, pity: (PityKind, PityCounters)) -> Self {
+        RollAudit {
+            index,
+            source: source.to_string(),
+            candidates: vec![],
+            roll: None,
+            item_index: None,
+            rarity: item.rarity,
+            item: item.name.clone(),
+            pity_strategy: pity.0.to_string(),
+            pity_state: pity.1,
+            pity_adjusted: false,
+        }
+    }
+}
+
+/// Rarity weights a roll was made against, and the raw roll within their total.
+#[derive(Debug)]
+struct RollSample {
+    candidates: Vec<(Rarity, u64)>,
+    roll: u64,
+    pity_adjusted: bool,
+}
+
+/// Item pools keyed by rarity.
+///
+/// Kept in rarity order (best first) so exports, logs and anything iterating the pools are
+/// stable between runs, which a `HashMap` doesn't guarantee.
+#[derive(Debug, Clone, Default)]
+pub struct ItemPools(BTreeMap<Rarity, Vec<GachaItem>>);
+
+impl Deref for ItemPools {
+    type Target = BTreeMap<Rarity, Vec<GachaItem>>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromIterator<(Rarity, Vec<GachaItem>)> for ItemPools {
+    fn from_iter<T: IntoIterator<Item = (Rarity, Vec<GachaItem>)>>(iter: T) -> Self {
+        ItemPools(iter.into_iter().collect())
+    }
+}
+
+impl ItemPools {
+    fn to_dictionary(&self) -> Dictionary {
+        // Godot dictionaries keep insertion order
+        self.0
+            .iter()
+            .map(|(rarity, items)| (*rarity, items.to_variant()))
+            .collect::<Dictionary<Unique>>()
+            .into_shared()
+    }
+
+    /// Convert a `{ Rarity: [GachaItem] }` Dictionary, naming the offending entry on failure.
+    pub(crate) fn from_dictionary(dict: &Dictionary) -> Result<Self> {
+        let mut pools = BTreeMap::new();
+        for (key, items) in dict.iter() {
+            let rarity = Rarity::from_variant(&key).map_err(|e| {
+                GachaError::InvalidProperty(
+                    format!("data[{key}]"),
+                    format!("not a Rarity key: {e}"),
+                )
+            })?;
+            let path = format!("data[{rarity:?}]");
+            let items = VariantArray::from_variant(&items).map_err(|e| {
+                GachaError::InvalidProperty(
+                    path.clone(),
+                    format!("expected an Array of items: {e}"),
+                )
+            })?;
+            pools.insert(rarity, items_from_array(&items, &path)?);
+        }
+        Ok(ItemPools(pools))
+    }
+}
+
+impl ToVariant for ItemPools {
+    fn to_variant(&self) -> Variant {
+        self.to_dictionary().to_variant()
+    }
+}
+
+impl FromVariant for ItemPools {
+    fn from_variant(variant: &Variant) -> std::result::Result<Self, FromVariantError> {
+        let dict = Dictionary::from_variant(variant)?;
+        Self::from_dictionary(&dict).map_err(|e| FromVariantError::Custom(e.to_string()))
+    }
+}
+
+impl Export for ItemPools {
+    type Hint = ();
+    fn export_info(_hint: Option<Self::Hint>) -> ExportInfo {
+        ExportInfo::new(VariantType::Dictionary)
+    }
+}
+
 #[derive(NativeClass, Debug, Default)]
 #[inherit(Node)]
+#[register_with(Self::register)]
 pub struct GachaSystem {
     #[property]
     chances: u32,
@@ -37,14 +209,132 @@ pub struct GachaSystem {
     pity: u32,
     #[property]
     hard_pity: u32,
+    /// How items are picked once the rarity is decided, exported manually in [`Self::register`]
+    /// as one of `"uniform"`, `"weighted"` or `"box"`.
+    item_sampler: ItemSampler,
+    /// Pull without an SSR from which `soft_pity_step` is added to the SSR chance on every
+    /// pull, on top of `pity_strategy`. `0` disables the ramp.
+    #[property]
+    soft_pity_start: u32,
+    /// SSR chance added per pull from `soft_pity_start` on, e.g. `0.06` for +6%.
+    #[property]
+    soft_pity_step: f64,
+    /// Pity design applied to `pity`/`hard_pity`, exported manually in [`Self::register`]
+    /// as one of `"none"`, `"hard_cutoff"`, `"per_rarity"` or `"soft_curve"`.
+    pity_strategy: PityKind,
     /// Accumulated ammount of pulls before hitting any pity.
     _pity_accu: u32,
     /// Accumulated ammount of pulls before hitting hard pity.
     _hard_pity_accu: u32,
+    /// Exported manually in [`Self::register`] to report conversion errors.
+    data: ItemPools,
+    /// Exported manually in [`Self::register`] to report conversion errors.
+    rarities: Vec<(Rarity, f64)>,
+    /// Names of the SSR items on rate-up, see `featured_rate`.
     #[property]
-    data: HashMap<Rarity, Vec<GachaItem>>,
+    featured_items: Vec<String>,
+    /// Share of SSR pulls that go to `featured_items`. Losing this 50/50 guarantees a featured
+    /// item on the next SSR. Not applied with the `box` item sampler.
     #[property]
-    rarities: Vec<(Rarity, f64)>,
+    featured_rate: f64,
+    /// The last SSR wasn't featured, so the next one will be.
+    _featured_guarantee: bool,
+    /// Banners pulled from besides the standard one made of `data` and `rarities`, keyed by id.
+    ///
+    /// Exported manually in [`Self::register`] as `{ id: { data, rarities } }`.
+    banners: BTreeMap<String, Banner>,
+    /// Names of items taken out of the pools at runtime, e.g. a bugged unit flagged by remote config.
+    ///
+    /// Exported manually in [`Self::register`] to reset the item sampler when it changes.
+    disabled_items: Vec<String>,
+    /// Predefined results handed out in order before any RNG is involved,
+    /// each entry is consumed once (e.g. the tutorial's starter SSR).
+    ///
+    /// Exported manually in [`Self::register`] to report conversion errors.
+    scripted_results: Vec<GachaItem>,
+    /// `scripted_results` as last set, handed out again after `reset_profile`.
+    _configured_scripted_results: Vec<GachaItem>,
+    /// Description of the last property value that couldn't be converted.
+    _last_conversion_error: Option<String>,
+    /// Record raw rolls of each pull for `get_last_pull_audit`, always on in debug builds.
+    #[property]
+    audit_rolls: bool,
+    /// Whether the engine runs a debug build, detected in `_ready`.
+    _debug_build: bool,
+    /// Roll records of the last pull.
+    _last_audit: Vec<RollAudit>,
+    /// Set by `_ready` when `data` or `rarities` are unusable, pulls are refused until fixed.
+    _config_error: Option<String>,
+    /// Allow a rarity resolver to be registered in non-debug builds.
+    #[property]
+    allow_rarity_resolver: bool,
+    /// Script callback that can force the rarity of a pull, see [`Self::set_rarity_resolver`].
+    rarity_resolver: Option<Ref<FuncRef>>,
+    /// One-time token that has to be passed back to [`Self::reset_profile`].
+    _reset_token: Option<String>,
+    /// How chatty pulls are in the output: 0 = silent, 1 = batch summaries, 2 = individual rolls.
+    #[property]
+    verbosity: u32,
+    /// When logging individual rolls, only print 1 in N of them.
+    #[property]
+    log_sample_rate: u32,
+    /// Most pulls allowed per UTC day, for minor-protection limits. 0 = unlimited.
+    #[property]
+    daily_pull_limit: u32,
+    /// Most pulls allowed per UTC calendar month. 0 = unlimited.
+    #[property]
+    monthly_pull_limit: u32,
+    /// Pulls counted towards the limits, kept in memory only until the profile is saved.
+    _spend: SpendTracker,
+    /// Pulls in a session after which the next pull waits for `confirm_spend`. 0 = never.
+    #[property]
+    confirm_spend_after: u32,
+    /// Pulls since the session started or the last `confirm_spend`.
+    _session_pulls: u32,
+    /// Token `confirm_spend` expects while a confirmation is pending.
+    _spend_token: Option<String>,
+    /// Random streams of this node, seeded from entropy on first use or by `start_recording`.
+    _rng: Option<RngStreams>,
+    /// Session being recorded, see [`Self::start_recording`].
+    _recording: Option<Recording>,
+    /// Roll each pull from its own seed, committed to before the pull and revealed after it.
+    #[property]
+    verifiable_pulls: bool,
+    /// Seed of the next verifiable pull, its commitment may already be published.
+    _fairness_seed: Option<u64>,
+    /// Seed and commitment of the last verifiable pull.
+    _last_fairness: Option<FairnessProof>,
+    /// Rolls seen since the node was created, used for log sampling.
+    _rolls_seen: u64,
+    /// Share `chances`, pity, spend and session counters, the remaining `scripted_results` and
+    /// pending confirmation tokens with the other nodes of this profile, so they carry over scene
+    /// changes. Empty keeps the counters to this node.
+    #[property]
+    profile: String,
+    /// Counters of `profile`, set in `_ready`.
+    _shared: Option<SharedCounters>,
+    /// `save_state` file loaded on `_ready` by the first node of the profile, and saved back on
+    /// `_exit_tree`, on quit or when the app is paused. Empty leaves saving to the game.
+    #[property]
+    save_path: String,
+    /// Chance for `pull_with_reveal` to present a batch with an SR/SSR as one rarity lower first.
+    #[property]
+    upgrade_tease_chance: f64,
+    /// Rarities (e.g. `"SSR"`) and item names whose pulls emit `broadcast_obtained`.
+    #[property]
+    broadcast: Vec<String>,
+    /// Name of the player announced by `broadcast_obtained`.
+    #[property]
+    player_name: String,
+    /// Announce the player with only the first letter of `player_name` visible.
+    #[property]
+    broadcast_anonymize: bool,
+    /// Decimals of the percentages returned by `get_rate_disclosure`, at most 6.
+    #[property]
+    rate_decimals: u32,
+    /// How `get_rate_disclosure` rounds to `rate_decimals`, exported manually in
+    /// [`Self::register`] as one of `"half_even"`, `"half_up"` or `"truncate"`.
+    rate_rounding: RateRounding,
 }
 
 #[methods]
@@ -62,52 +352,1197 @@ impl GachaSystem {
             rarities: default_rarities,
             // TODO: set to 0 before publish
             chances: 100,
+            verbosity: VERBOSITY_SUMMARY,
+            log_sample_rate: 1,
+            upgrade_tease_chance: 0.25,
+            rate_decimals: 2,
+            featured_rate: 0.5,
+            soft_pity_step: 0.06,
+            save_path: "user://gacha.cfg".into(),
             ..Default::default()
         }
     }
 
+    fn register(builder: &ClassBuilder<Self>) {
+        builder
+            .signal("pity_progress_changed")
+            .with_param("progress", VariantType::Dictionary)
+            .done();
+        builder
+            .signal("spending_limit_reached")
+            .with_param("period", VariantType::GodotString)
+            .with_param("limit", VariantType::I64)
+            .with_param("message", VariantType::GodotString)
+            .done();
+        builder
+            .signal("confirmation_required")
+            .with_param("token", VariantType::GodotString)
+            .with_param("session_pulls", VariantType::I64)
+            .done();
+        builder
+            .signal("broadcast_obtained")
+            .with_param("player_name", VariantType::GodotString)
+            .with_param("item", VariantType::Dictionary)
+            .done();
+        builder
+            .signal("configuration_error")
+            .with_param("message", VariantType::GodotString)
+            .done();
+
+        // Collection properties take raw Dictionary/Array values and convert them here, so a bad
+        // entry from the inspector is reported precisely instead of as a generic type mismatch.
+        builder
+            .property::<Dictionary>("data")
+            .with_getter(|this: &Self, _| this.data.to_dictionary())
+            .with_setter(|this: &mut Self, _, value: Dictionary| {
+                if let Some(data) = this.record_conversion(ItemPools::from_dictionary(&value)) {
+                    this.data = data;
+                    this.item_sampler.as_sampler().reset();
+                    this.revalidate();
+                }
+            })
+            .done();
+        builder
+            .property::<VariantArray>("rarities")
+            .with_getter(|this: &Self, _| to_array(&this.rarities))
+            .with_setter(|this: &mut Self, _, value: VariantArray| {
+                let rates = rates_from_array(&value, "rarities");
+                if let Some(rarities) = this.record_conversion(rates) {
+                    this.rarities = rarities;
+                    this.revalidate();
+                }
+            })
+            .done();
+        builder
+            .property::<Dictionary>("banners")
+            .with_getter(|this: &Self, _| banners_to_dictionary(&this.banners))
+            .with_setter(|this: &mut Self, _, value: Dictionary| {
+                let banners = banners_from_dictionary(&value, &this.banners, &this.item_sampler);
+                if let Some(banners) = this.record_conversion(banners) {
+                    this.banners = banners;
+                }
+            })
+            .done();
+        builder
+            .property::<VariantArray>("scripted_results")
+            .with_getter(|this: &Self, _| to_array(&this.scripted_results))
+            .with_setter(|this: &mut Self, _, value: VariantArray| {
+                let items = items_from_array(&value, "scripted_results");
+                if let Some(items) = this.record_conversion(items) {
+                    this.set_scripted_results(items);
+                }
+            })
+            .done();
+        builder
+            .property::<Vec<String>>("disabled_items")
+            .with_getter(|this: &Self, _| this.disabled_items.clone())
+            .with_setter(|this: &mut Self, _, value: Vec<String>| {
+                this.disabled_items = value;
+                // box contents are indices into the enabled items
+                this.item_sampler.as_sampler().reset();
+                for banner in this.banners.values_mut() {
+                    banner.item_sampler.as_sampler().reset();
+                }
+            })
+            .done();
+        builder
+            .property::<String>("item_sampler")
+            .with_default(ItemSampler::default().to_string())
+            .with_hint(StringHint::Enum(EnumHint::new(
+                ItemSampler::NAMES.iter().map(ToString::to_string).collect(),
+            )))
+            .with_getter(|this: &Self, _| this.item_sampler.to_string())
+            .with_setter(|this: &mut Self, _, value: String| {
+                if let Some(sampler) = this.record_conversion(value.parse::<ItemSampler>()) {
+                    for banner in this.banners.values_mut() {
+                        banner.item_sampler = sampler.fresh();
+                    }
+                    this.item_sampler = sampler;
+                }
+            })
+            .done();
+        builder
+            .property::<String>("pity_strategy")
+            .with_default(PityKind::default().to_string())
+            .with_hint(StringHint::Enum(EnumHint::new(
+                PityKind::ALL.iter().map(ToString::to_string).collect(),
+            )))
+            .with_getter(|this: &Self, _| this.pity_strategy.to_string())
+            .with_setter(|this: &mut Self, _, value: String| {
+                if let Some(kind) = this.record_conversion(value.parse()) {
+                    this.pity_strategy = kind;
+                }
+            })
+            .done();
+        builder
+            .property::<String>("rate_rounding")
+            .with_default(RateRounding::default().to_string())
+            .with_hint(StringHint::Enum(EnumHint::new(
+                RateRounding::NAMES
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect(),
+            )))
+            .with_getter(|this: &Self, _| this.rate_rounding.to_string())
+            .with_setter(|this: &mut Self, _, value: String| {
+                if let Some(rounding) = this.record_conversion(value.parse()) {
+                    this.rate_rounding = rounding;
+                }
+            })
+            .done();
+    }
+
+    /// Keep the error of a failed property conversion for `last_conversion_error`.
+    fn record_conversion<T>(&mut self, converted: Result<T>) -> Option<T> {
+        match converted {
+            Ok(value) => Some(value),
+            Err(e) => {
+                godot_error!("{e}");
+                self._last_conversion_error = Some(e.to_string());
+                None
+            }
+        }
+    }
+
+    /// Describe the last pool, rate or item value rejected when set from GDScript
+    /// or the inspector, `null` if everything converted fine so far.
+    #[method]
+    fn last_conversion_error(&self) -> Option<String> {
+        self._last_conversion_error.clone()
+    }
+
     #[method]
-    fn _ready(&self) {
+    fn _ready(&mut self, #[base] owner: &Node) {
+        self._debug_build = OS::godot_singleton().is_debug_build();
         godot_print!("rarities: {:?}", self.rarities);
+        if let Some(problem) = self.configuration_problem() {
+            godot_error!("GachaSystem disabled: {problem}");
+            owner.emit_signal("configuration_error", &[problem.to_variant()]);
+            self._config_error = Some(problem);
+        }
+        let mut first_of_profile = true;
+        if !self.profile.is_empty() {
+            let shared = shared_counters(&self.profile);
+            // the first node of the profile brings the counters, later ones pick them up
+            if shared.get().is_none() {
+                shared.set(self.profile_counters());
+            } else {
+                first_of_profile = false;
+            }
+            self._shared = Some(shared);
+            self.adopt_shared();
+        }
+        // later nodes would replace the profile's counters with the older saved ones
+        if first_of_profile && !self.save_path.is_empty() {
+            self.load_state(self.save_path.clone());
+        }
+    }
+
+    #[method]
+    fn _exit_tree(&mut self) {
+        self.autosave();
+    }
+
+    /// Also save when the window is closed or the app goes to the background, mobile systems
+    /// kill paused apps without `_exit_tree` ever running.
+    #[method]
+    fn _notification(&mut self, what: i64) {
+        if what == Node::NOTIFICATION_WM_QUIT_REQUEST || what == Node::NOTIFICATION_APP_PAUSED {
+            self.autosave();
+        }
+    }
+
+    fn autosave(&mut self) {
+        if !self.save_path.is_empty() {
+            self.save_state(self.save_path.clone());
+        }
+    }
+
+    fn profile_counters(&self) -> ProfileCounters {
+        ProfileCounters {
+            chances: self.chances,
+            pity_count: self._pity_accu,
+            hard_pity_count: self._hard_pity_accu,
+            spend: self._spend.clone(),
+            session_pulls: self._session_pulls,
+            featured_guarantee: self._featured_guarantee,
+            scripted_results: self.scripted_results.clone(),
+            spend_token: self._spend_token.clone(),
+            reset_token: self._reset_token.clone(),
+            banners: self
+                .banners
+                .iter()
+                .map(|(id, banner)| (id.clone(), banner.counters()))
+                .collect(),
+        }
+    }
+
+    /// Pick up counters changed by other nodes of the profile, before using them.
+    fn adopt_shared(&mut self) {
+        let Some(counters) = self._shared.as_ref().and_then(SharedCounters::get) else {
+            return;
+        };
+        self.chances = counters.chances;
+        self._pity_accu = counters.pity_count;
+        self._hard_pity_accu = counters.hard_pity_count;
+        self._spend = counters.spend;
+        self._session_pulls = counters.session_pulls;
+        self._featured_guarantee = counters.featured_guarantee;
+        self.scripted_results = counters.scripted_results;
+        self._spend_token = counters.spend_token;
+        self._reset_token = counters.reset_token;
+        for (id, counters) in counters.banners {
+            if let Some(banner) = self.banners.get_mut(&id) {
+                banner.set_counters(counters);
+            }
+        }
+    }
+
+    /// Hand counters changed by this node to the other nodes of the profile.
+    fn publish_shared(&self) {
+        if let Some(shared) = &self._shared {
+            shared.set(self.profile_counters());
+        }
+    }
+
+    /// [`API_VERSION`] of this build, for the facade to check it matches the game scripts.
+    #[method]
+    fn api_version(&self) -> String {
+        API_VERSION.to_string()
+    }
+
+    /// Whether pulls are refused because of a configuration error found in `_ready`.
+    #[method]
+    fn is_disabled(&self) -> bool {
+        self._config_error.is_some()
+    }
+
+    /// Check that there is something to pull from, `None` if the node is usable.
+    fn configuration_problem(&self) -> Option<String> {
+        pool_problem(&self.data, &self.rarities)
+    }
+
+    /// Re-enable a disabled node once its configuration has been fixed.
+    fn revalidate(&mut self) {
+        if self._config_error.is_some() {
+            self._config_error = self.configuration_problem();
+        }
+    }
+
+    /// Pull up to `num` items from the banner `banner_id`, see `list_banners`.
+    #[method]
+    fn pull(&mut self, #[base] owner: &Node, banner_id: String, num: u32) -> Vec<GachaItem> {
+        self.pull_and_notify(owner, &banner_id, num, false).0
+    }
+
+    /// Same as `pull`, also returning how the reveal scene should present the results.
+    ///
+    /// Returns a Dictionary with `items` and `reveal`, the latter is `null` if nothing was pulled,
+    /// otherwise it holds the `initial` and `actual` rarity and the `upgrade_index` of the item
+    /// that triggers the upgrade (`null` when there's no tease). `confirmation_required` holds
+    /// the token for `confirm_spend` when the pull was held back for a confirmation.
+    /// `fairness` is the proof of the pull when `verifiable_pulls` is set, see
+    /// `get_last_fairness_proof`.
+    #[method]
+    fn pull_with_reveal(
+        &mut self,
+        #[base] owner: &Node,
+        banner_id: String,
+        num: u32,
+    ) -> Dictionary {
+        let (items, reveal) = self.pull_and_notify(owner, &banner_id, num, true);
+
+        let result = Dictionary::new();
+        result.insert("items", items);
+        result.insert("reveal", reveal);
+        result.insert("confirmation_required", self._spend_token.clone());
+        result.insert("fairness", self._last_fairness.clone());
+        result.into_shared()
+    }
+
+    /// Token for `confirm_spend` once the session went past `confirm_spend_after` pulls,
+    /// the same token is handed out until it's confirmed.
+    fn spend_confirmation(&mut self) -> Option<String> {
+        if self.confirm_spend_after == 0 || self._session_pulls < self.confirm_spend_after {
+            return None;
+        }
+        Some(self._spend_token.get_or_insert_with(new_token).clone())
+    }
+
+    /// Token of the pending spend confirmation, `null` if pulls aren't held back.
+    #[method]
+    fn pending_spend_confirmation(&mut self) -> Option<String> {
+        self.adopt_shared();
+        self._spend_token.clone()
+    }
+
+    /// Let pulls through again after the player confirmed they want to keep spending,
+    /// allowing another `confirm_spend_after` pulls before asking again.
+    ///
+    /// `token` comes from the `confirmation_required` signal, returns `false` if it doesn't match.
+    #[method]
+    fn confirm_spend(&mut self, token: String) -> bool {
+        self.adopt_shared();
+        if self._spend_token.as_deref() != Some(token.as_str()) {
+            godot_warn!("spend confirmation rejected: no pending confirmation for this token");
+            return false;
+        }
+        self._spend_token = None;
+        self._session_pulls = 0;
+        self.publish_shared();
+        true
+    }
+
+    fn pull_and_notify(
+        &mut self,
+        owner: &Node,
+        banner_id: &str,
+        num: u32,
+        with_reveal: bool,
+    ) -> (Vec<GachaItem>, Option<RevealPlan>) {
+        if let Some(problem) = &self._config_error {
+            godot_error!("pull refused, GachaSystem is disabled: {problem}");
+            return (vec![], None);
+        }
+        if !self.has_banner(banner_id) {
+            godot_error!("pull refused, no banner {banner_id:?}");
+            return (vec![], None);
+        }
+        self.adopt_shared();
+        if let Some(token) = self.spend_confirmation() {
+            self.publish_shared();
+            owner.emit_signal(
+                "confirmation_required",
+                &[token.to_variant(), self._session_pulls.to_variant()],
+            );
+            return (vec![], None);
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        // pulls the chances don't cover can't hit a limit
+        let wanted = num.min(self.chances);
+        let (allowed, limited) =
+            self._spend
+                .allowance(now, self.daily_pull_limit, self.monthly_pull_limit, wanted);
+        if let Some((period, limit)) = limited {
+            let e = GachaError::SpendingLimit(period.to_string(), limit, allowed);
+            godot_warn!("pull limited: {e}");
+            owner.emit_signal(
+                "spending_limit_reached",
+                &[
+                    period.to_string().to_variant(),
+                    limit.to_variant(),
+                    e.to_string().to_variant(),
+                ],
+            );
+        }
+        let (items, reveal) = self
+            .pull_banner(banner_id, allowed, with_reveal)
+            .unwrap_or_default();
+        self._spend.record(now, items.len() as u32);
+        self._session_pulls += items.len() as u32;
+        self.publish_shared();
+        for item in self.broadcasts(&items) {
+            owner.emit_signal(
+                "broadcast_obtained",
+                &[self.broadcast_name().to_variant(), item.to_variant()],
+            );
+        }
+        if !items.is_empty() {
+            owner.emit_signal(
+                "pity_progress_changed",
+                &[self
+                    .get_pity_progress(banner_id.to_string())
+                    .owned_to_variant()],
+            );
+        }
+        (items, reveal)
+    }
+
+    /// Pulled items listed in `broadcast`, by rarity or by name.
+    fn broadcasts<'a>(&self, items: &'a [GachaItem]) -> Vec<&'a GachaItem> {
+        items
+            .iter()
+            .filter(|item| {
+                self.broadcast
+                    .iter()
+                    .any(|entry| *entry == item.name || *entry == format!("{:?}", item.rarity))
+            })
+            .collect()
+    }
+
+    /// `player_name` as announced, e.g. `"A****"` with `broadcast_anonymize` set.
+    fn broadcast_name(&self) -> String {
+        if !self.broadcast_anonymize {
+            return self.player_name.clone();
+        }
+        self.player_name
+            .chars()
+            .enumerate()
+            .map(|(idx, c)| if idx == 0 { c } else { '*' })
+            .collect()
+    }
+
+    /// Ids of the banners that can be pulled from, `"standard"` first.
+    #[method]
+    fn list_banners(&self) -> Vec<String> {
+        let others = self.banners.keys().cloned();
+        std::iter::once(STANDARD_BANNER.to_string())
+            .chain(others)
+            .collect()
+    }
+
+    fn has_banner(&self, banner_id: &str) -> bool {
+        banner_id == STANDARD_BANNER || self.banners.contains_key(banner_id)
+    }
+
+    /// Run `f` with the banner `banner_id` swapped in for the node's pool, rates and pity
+    /// progress, `None` if there's no such banner.
+    fn with_banner<T>(&mut self, banner_id: &str, f: impl FnOnce(&mut Self) -> T) -> Option<T> {
+        if banner_id == STANDARD_BANNER {
+            return Some(f(self));
+        }
+        let mut banner = self.banners.remove(banner_id)?;
+        self.swap_banner(&mut banner);
+        let result = f(self);
+        self.swap_banner(&mut banner);
+        self.banners.insert(banner_id.to_string(), banner);
+        Some(result)
+    }
+
+    fn swap_banner(&mut self, banner: &mut Banner) {
+        mem::swap(&mut self.data, &mut banner.data);
+        mem::swap(&mut self.rarities, &mut banner.rarities);
+        mem::swap(&mut self.item_sampler, &mut banner.item_sampler);
+        mem::swap(&mut self._pity_accu, &mut banner.pity_count);
+        mem::swap(&mut self._hard_pity_accu, &mut banner.hard_pity_count);
+        mem::swap(&mut self.featured_items, &mut banner.featured_items);
+        mem::swap(&mut self.featured_rate, &mut banner.featured_rate);
+        mem::swap(
+            &mut self._featured_guarantee,
+            &mut banner.featured_guarantee,
+        );
+    }
+
+    fn pull_banner(
+        &mut self,
+        banner_id: &str,
+        num: u32,
+        with_reveal: bool,
+    ) -> Option<(Vec<GachaItem>, Option<RevealPlan>)> {
+        self.with_banner(banner_id, |this| this.run_pull(banner_id, num, with_reveal))
+    }
+
+    /// Pull and plan the reveal if asked to, recording the call when a recording is running.
+    fn run_pull(
+        &mut self,
+        banner_id: &str,
+        num: u32,
+        with_reveal: bool,
+    ) -> (Vec<GachaItem>, Option<RevealPlan>) {
+        let items = self.pull_items(num);
+        let reveal = if with_reveal {
+            let mut streams = self.take_rng();
+            let reveal = plan_reveal(
+                &items,
+                self.upgrade_tease_chance,
+                streams.stream(rng::REVEAL),
+            );
+            self._rng = Some(streams);
+            reveal
+        } else {
+            None
+        };
+        if let Some(recording) = &mut self._recording {
+            let command = RecordedCommand::new(banner_id, num, &items, reveal.clone(), with_reveal);
+            recording.commands.push(command);
+        }
+        (items, reveal)
+    }
+
+    /// Take the RNG streams out for a pull, they have to be put back into `_rng` afterwards.
+    fn take_rng(&mut self) -> RngStreams {
+        self._rng.take().unwrap_or_else(RngStreams::from_entropy)
     }
 
+    /// Current progress of the banner `banner_id` towards soft and hard pity, for binding
+    /// progress bars.
+    ///
+    /// `soft` and `hard` are fractions in `0..=1`, where `1` means the next pull is guaranteed
+    /// and `0` is kept for a guarantee `pity_strategy` doesn't make. `ramp_progress` is the
+    /// fraction towards `ramp_start` (`soft_pity_start`), `1` once the soft pity ramp raises
+    /// the SSR chance and `0` while it's disabled. Alongside are the raw counters (`pity_count`,
+    /// `hard_pity_count`), thresholds (`pity`, `hard_pity`) and the `banner` id. Empty if
+    /// there's no such banner.
     #[method]
-    fn pull(&mut self, num: u32) -> Vec<GachaItem> {
+    fn get_pity_progress(&mut self, banner_id: String) -> Dictionary {
+        self.adopt_shared();
+        self.with_banner(&banner_id, |this| this.pity_progress(&banner_id))
+            .unwrap_or_else(|| Dictionary::new().into_shared())
+    }
+
+    fn pity_progress(&self, banner_id: &str) -> Dictionary {
+        let progress = Dictionary::new();
+        progress.insert("banner", banner_id);
+        let fractions = self.pity_strategy.strategy().progress(self.pity_counters());
+        progress.insert("soft", fractions.soft);
+        progress.insert("hard", fractions.hard);
+        progress.insert("ramp_start", self.soft_pity_start);
+        progress.insert(
+            "ramp_progress",
+            self.soft_ramp().fraction(self._hard_pity_accu),
+        );
+        progress.insert("pity_count", self._pity_accu);
+        progress.insert("hard_pity_count", self._hard_pity_accu);
+        progress.insert("pity", self.pity);
+        progress.insert("hard_pity", self.hard_pity);
+        progress.insert("featured_guarantee", self._featured_guarantee);
+        progress.into_shared()
+    }
+
+    fn pull_items(&mut self, num: u32) -> Vec<GachaItem> {
         let mut result = vec![];
-        let mut rng = thread_rng();
+        let mut streams = self.take_rng();
+        self._last_fairness = None;
+        let mut verifiable_rng = self.verifiable_pulls.then(|| {
+            // each verifiable pull rolls from its own committed seed, revealed once it's done
+            let seed = self.next_fairness_seed(&mut streams);
+            self._fairness_seed = None;
+            self._last_fairness = Some(FairnessProof::new(seed));
+            ChaCha8Rng::seed_from_u64(seed)
+        });
+        let rng = match &mut verifiable_rng {
+            Some(rng) => rng,
+            None => streams.stream(rng::GACHA),
+        };
         let num_limit = num.min(self.chances);
+        let auditing = self.audit_rolls || self._debug_build;
+        self._last_audit.clear();
 
-        for _ in 0..num_limit {
-            let maybe_rarities = self.pity_rarities_and_rate();
-            let available_rarities = maybe_rarities.as_ref().unwrap_or(&self.rarities);
-            let gen_limit: f64 = available_rarities.iter().map(|(_, ra)| ra).sum();
-            // generate a random float within the limit
-            let f = rng.gen_range(0.0..gen_limit);
-            let (pull_result, _) = rarity_range(available_rarities)
-                .iter()
-                .find(|(_, range)| range.contains(&f))
-                .expect(&format!(
-                    "unknown error: invalid gacha pull with random number '{f}'"
-                ))
-                .to_owned();
-            // NB: `godot_xxx` macros are not working with cargo test, so comment this before running tests
-            godot_print!("rolled: {f}, you got a: {:?} item", pull_result);
-            result.push(self.gacha_by_rarity(pull_result, &mut rng).unwrap());
+        for index in 0..num_limit {
+            let pity = (self.pity_strategy, self.pity_counters());
+            if let Some(item) = self.next_scripted_result() {
+                self.log_roll(|| format!("scripted result, you got: {item:?}"));
+                self.mark_unverified(index);
+                if auditing {
+                    self._last_audit
+                        .push(RollAudit::new(index, "scripted", &item, pity));
+                }
+                result.push(item);
+                continue;
+            }
+            let (source, rolled) = match self.resolved_rarity(index, num_limit) {
+                Some(forced) => {
+                    self.log_roll(|| format!("rarity resolver forced a: {forced:?} item"));
+                    self.mark_unverified(index);
+                    ("resolver", Ok((forced, None)))
+                }
+                None => {
+                    let rolled = self.roll_rarity(rng);
+                    (
+                        "roll",
+                        rolled.map(|(rarity, sample)| (rarity, Some(sample))),
+                    )
+                }
+            };
+            let picked = rolled.and_then(|(rarity, sample)| {
+                let (item_index, item) = self.gacha_by_rarity(rarity, rng)?;
+                Ok((sample, item_index, item))
+            });
+            match picked {
+                Ok((sample, item_index, item)) => {
+                    if auditing {
+                        let mut audit = RollAudit::new(index, source, &item, pity);
+                        audit.item_index = Some(item_index);
+                        if let Some(sample) = sample {
+                            audit.candidates = sample.candidates;
+                            audit.roll = Some(sample.roll);
+                            audit.pity_adjusted = sample.pity_adjusted;
+                        }
+                        self._last_audit.push(audit);
+                    }
+                    result.push(item);
+                }
+                Err(e) => {
+                    godot_error!("pull stopped after {} item(s): {e}", result.len());
+                    break;
+                }
+            }
         }
+        self._rng = Some(streams);
+        self.log_summary(&result);
         result
     }
 
-    fn gacha_by_rarity(&mut self, rarity: Rarity, rng: &mut ThreadRng) -> Result<GachaItem> {
-        let poll = self
+    /// Note in the fairness proof of this pull, if any, that the seed didn't decide pull `index`.
+    fn mark_unverified(&mut self, index: u32) {
+        if let Some(proof) = &mut self._last_fairness {
+            proof.unverified.push(index);
+        }
+    }
+
+    /// Seed the next verifiable pull will use, drawn ahead of time so it can be committed to.
+    fn next_fairness_seed(&mut self, streams: &mut RngStreams) -> u64 {
+        *self
+            ._fairness_seed
+            .get_or_insert_with(|| streams.stream(rng::FAIRNESS).gen())
+    }
+
+    /// SHA-256 commitment to the seed of the next pull, `null` unless `verifiable_pulls` is set.
+    ///
+    /// Publish it before pulling, the pull then reveals the seed in `get_last_fairness_proof`.
+    #[method]
+    fn get_fairness_commitment(&mut self) -> Option<String> {
+        if !self.verifiable_pulls {
+            return None;
+        }
+        let mut streams = self.take_rng();
+        let seed = self.next_fairness_seed(&mut streams);
+        self._rng = Some(streams);
+        Some(FairnessProof::new(seed).commitment)
+    }
+
+    /// Seed of the last verifiable pull and the commitment published for it, or `null`.
+    #[method]
+    fn get_last_fairness_proof(&self) -> Option<FairnessProof> {
+        self._last_fairness.clone()
+    }
+
+    /// Base rate of each rarity for the rates disclosure, e.g. `{ "SSR": "5.00%" }`.
+    ///
+    /// Rates are shares of the configured `rarities`, before any pity adjustment, formatted
+    /// with `rate_decimals` and `rate_rounding`.
+    #[method]
+    fn get_rate_disclosure(&self) -> Dictionary {
+        let disclosure = Dictionary::new();
+        let rates = disclose_rates(
+            &rarity_weights(&self.rarities),
+            self.rate_decimals,
+            self.rate_rounding,
+        );
+        for (rarity, rate) in rates {
+            disclosure.insert(format!("{rarity:?}"), rate);
+        }
+        disclosure.into_shared()
+    }
+
+    /// Save chances, pity progress, spend counters and the remaining scripted results to `path`,
+    /// e.g. `user://gacha.cfg`. Banner pools and rates aren't saved, they come from the game
+    /// data, and there's no pull history to save since pulls aren't kept after they're returned.
+    #[method]
+    fn save_state(&mut self, path: String) -> bool {
+        self.adopt_shared();
+        let file = ConfigFile::new();
+        self.saved_state().write(&file);
+        match file.save(path.as_str()) {
+            Ok(()) => true,
+            Err(e) => {
+                godot_error!("could not save gacha state to {path}: {e}");
+                false
+            }
+        }
+    }
+
+    /// Restore the state saved with `save_state`. Keys missing from older saves keep their
+    /// current values, and a missing file counts as nothing saved yet.
+    #[method]
+    fn load_state(&mut self, path: String) -> bool {
+        let file = ConfigFile::new();
+        match file.load(path.as_str()) {
+            Ok(()) => {}
+            Err(GodotError::FileNotFound) => return true,
+            Err(e) => {
+                godot_error!("could not load gacha state from {path}: {e}");
+                return false;
+            }
+        }
+        match SavedState::read(&file) {
+            Ok(state) => {
+                // counters the save doesn't have must not overwrite the profile's with stale ones
+                self.adopt_shared();
+                self.restore_state(state);
+                self.publish_shared();
+                true
+            }
+            Err(e) => {
+                godot_error!("could not load gacha state from {path}: {e}");
+                false
+            }
+        }
+    }
+
+    fn saved_state(&mut self) -> SavedState {
+        let banners = self
+            .list_banners()
+            .into_iter()
+            .filter_map(|id| {
+                let saved = self.with_banner(&id, |this| SavedBanner {
+                    pity_count: Some(this._pity_accu),
+                    hard_pity_count: Some(this._hard_pity_accu),
+                    featured_guarantee: Some(this._featured_guarantee),
+                    box_state: Some(this.item_sampler.box_state()),
+                })?;
+                Some((id, saved))
+            })
+            .collect();
+        SavedState {
+            config_hash: Some(self.config_hash()),
+            chances: Some(self.chances),
+            scripted_results: Some(self.scripted_results.clone()),
+            spend: Some(self._spend.clone()),
+            banners,
+        }
+    }
+
+    fn restore_state(&mut self, state: SavedState) {
+        // box indices only make sense for the pools they were saved with
+        let config_matches = state.config_hash == Some(self.config_hash());
+        if let Some(chances) = state.chances {
+            self.chances = chances;
+        }
+        if let Some(scripted_results) = state.scripted_results {
+            self.scripted_results = scripted_results;
+        }
+        if let Some(spend) = state.spend {
+            self._spend = spend;
+        }
+        for (id, saved) in state.banners {
+            let restored = self.with_banner(&id, |this| {
+                if let Some(count) = saved.pity_count {
+                    this._pity_accu = count;
+                }
+                if let Some(count) = saved.hard_pity_count {
+                    this._hard_pity_accu = count;
+                }
+                if let Some(guarantee) = saved.featured_guarantee {
+                    this._featured_guarantee = guarantee;
+                }
+                match &saved.box_state {
+                    Some(box_state) if config_matches => {
+                        this.item_sampler.restore_box_state(box_state);
+                    }
+                    _ => this.item_sampler.as_sampler().reset(),
+                }
+            });
+            if restored.is_none() {
+                godot_warn!("ignored saved state of unknown banner {id:?}");
+            }
+        }
+    }
+
+    /// Start recording pulls for a replayable bug report, restarting any recording in progress.
+    ///
+    /// Reseeds the RNG and snapshots the pity state, pulls are recorded until [`Self::stop_recording`].
+    /// Results forced by a rarity resolver can't be reproduced. A published fairness commitment
+    /// still holds for the next pull.
+    #[method]
+    fn start_recording(&mut self) {
+        self.adopt_shared();
+        let seed = thread_rng().gen();
+        self._rng = Some(RngStreams::new(seed));
+        // a commitment published before the recording still holds for the next pull
+        self._recording = Some(Recording {
+            version: RECORDING_VERSION,
+            config_hash: self.config_hash(),
+            seed,
+            fairness_seed: self._fairness_seed,
+            chances: self.chances,
+            pity_count: self._pity_accu,
+            hard_pity_count: self._hard_pity_accu,
+            featured_guarantee: self._featured_guarantee,
+            scripted_results: self.scripted_results.clone(),
+            box_state: self.item_sampler.box_state(),
+            banners: self
+                .banners
+                .iter()
+                .map(|(id, banner)| BannerState {
+                    id: id.clone(),
+                    pity_count: banner.pity_count,
+                    hard_pity_count: banner.hard_pity_count,
+                    featured_guarantee: banner.featured_guarantee,
+                    box_state: banner.item_sampler.box_state(),
+                })
+                .collect(),
+            commands: vec![],
+        });
+    }
+
+    /// Stop recording, returning the recording for `play_recording` or `null` if none was running.
+    #[method]
+    fn stop_recording(&mut self) -> Option<Recording> {
+        self._recording.take()
+    }
+
+    /// Replay a recording from `stop_recording` and report where the results diverge, if anywhere.
+    ///
+    /// Overwrites the pity state of this node and its `profile`, so it's only available in
+    /// debug builds.
+    #[method]
+    fn play_recording(&mut self, recording: Recording) -> Option<ReplayReport> {
+        if !self._debug_build {
+            godot_error!("play_recording is only available in debug builds");
+            return None;
+        }
+        let report = self.replay(&recording);
+        self.publish_shared();
+        Some(report)
+    }
+
+    fn replay(&mut self, recording: &Recording) -> ReplayReport {
+        if recording.version != RECORDING_VERSION {
+            godot_warn!(
+                "replaying a version {} recording with version {RECORDING_VERSION}",
+                recording.version
+            );
+        }
+        self.chances = recording.chances;
+        self._pity_accu = recording.pity_count;
+        self._hard_pity_accu = recording.hard_pity_count;
+        self._featured_guarantee = recording.featured_guarantee;
+        self.scripted_results = recording.scripted_results.clone();
+        let config_matches = recording.config_hash == self.config_hash();
+        // box indices only make sense for the pools they were recorded with
+        if config_matches {
+            self.item_sampler.restore_box_state(&recording.box_state);
+        } else {
+            self.item_sampler.as_sampler().reset();
+        }
+        for state in &recording.banners {
+            if let Some(banner) = self.banners.get_mut(&state.id) {
+                banner.pity_count = state.pity_count;
+                banner.hard_pity_count = state.hard_pity_count;
+                banner.featured_guarantee = state.featured_guarantee;
+                if config_matches {
+                    banner.item_sampler.restore_box_state(&state.box_state);
+                } else {
+                    banner.item_sampler.as_sampler().reset();
+                }
+            }
+        }
+        self._rng = Some(RngStreams::new(recording.seed));
+        // the commitment this node published stays valid for its next pull
+        let committed = mem::replace(&mut self._fairness_seed, recording.fairness_seed);
+        // don't record the replay into a recording that's in progress
+        let in_progress = self._recording.take();
+
+        let mut report = ReplayReport {
+            config_matches,
+            ..Default::default()
+        };
+        for (idx, expected) in recording.commands.iter().enumerate() {
+            let (items, reveal) = self
+                .pull_banner(&expected.banner, expected.num, expected.with_reveal())
+                .unwrap_or_default();
+            let actual = RecordedCommand::new(
+                &expected.banner,
+                expected.num,
+                &items,
+                reveal,
+                expected.with_reveal(),
+            );
+            if actual != *expected {
+                report.diverged_at = Some(idx);
+                report.expected = Some(expected.clone());
+                report.actual = Some(actual);
+                break;
+            }
+        }
+        self._recording = in_progress;
+        self._fairness_seed = committed;
+        report
+    }
+
+    /// Hash of everything that decides pull results besides the RNG and pity state: the pools,
+    /// rates, featured items and item sampler of every banner, the pity settings and the
+    /// disabled items. Settings that don't change which items are pulled, like
+    /// `upgrade_tease_chance` or `verifiable_pulls`, are left out.
+    fn config_hash(&self) -> String {
+        // saves and recordings keep the hash, so every field is written out in a fixed format
+        // rather than relying on `Debug` output staying the same between toolchains
+        let mut input = format!("gacha-config {CONFIG_HASH_VERSION}\n");
+        input += &format!(
+            "pity {} {} {} {} {}\n",
+            self.pity_strategy,
+            self.pity,
+            self.hard_pity,
+            self.soft_pity_start,
+            hash_float(self.soft_pity_step)
+        );
+        for name in &self.disabled_items {
+            input += &format!("disabled {}\n", hash_text(name));
+        }
+        input += &banner_config(
+            STANDARD_BANNER,
+            &self.data,
+            &self.rarities,
+            &self.item_sampler,
+            (&self.featured_items, self.featured_rate),
+        );
+        for (id, banner) in &self.banners {
+            input += &banner_config(
+                id,
+                &banner.data,
+                &banner.rarities,
+                &banner.item_sampler,
+                (&banner.featured_items, banner.featured_rate),
+            );
+        }
+        fairness::sha256_hex(&input)
+    }
+
+    /// Audit records of each roll made by the last pull, empty unless `audit_rolls`
+    /// is set or this is a debug build.
+    #[method]
+    fn get_last_pull_audit(&self) -> Vec<RollAudit> {
+        self._last_audit.clone()
+    }
+
+    /// Roll a rarity with the current rates, taking pity into account.
+    ///
+    /// Also returns the integer weights the roll was made against and the raw roll.
+    fn roll_rarity(&mut self, rng: &mut ChaCha8Rng) -> Result<(Rarity, RollSample)> {
+        let maybe_rarities = self.adjusted_rates();
+        let available_rarities = maybe_rarities.as_ref().unwrap_or(&self.rarities);
+        let (rarity, roll) = sample_rarity(available_rarities, rng)?;
+        let sample = RollSample {
+            candidates: rarity_weights(available_rarities),
+            roll: u64::try_from(roll).unwrap_or(u64::MAX),
+            pity_adjusted: maybe_rarities.is_some(),
+        };
+        self.log_roll(|| format!("rolled: {roll}, you got a: {rarity:?} item"));
+        Ok((rarity, sample))
+    }
+
+    /// Print a single roll if `verbosity` allows it, sampled by `log_sample_rate`.
+    ///
+    /// NB: `godot_xxx` macros are not working with cargo test, keep `verbosity` at 0 in tests.
+    fn log_roll(&mut self, msg: impl FnOnce() -> String) {
+        if self.verbosity < VERBOSITY_ROLLS {
+            return;
+        }
+        self._rolls_seen += 1;
+        if self
+            ._rolls_seen
+            .is_multiple_of(u64::from(self.log_sample_rate.max(1)))
+        {
+            godot_print!("{}", msg());
+        }
+    }
+
+    /// Print how many items of each rarity a batch produced.
+    fn log_summary(&self, result: &[GachaItem]) {
+        if self.verbosity < VERBOSITY_SUMMARY {
+            return;
+        }
+        let mut counts = BTreeMap::new();
+        for item in result {
+            *counts.entry(item.rarity).or_insert(0) += 1;
+        }
+        let counts: Vec<String> = counts
+            .iter()
+            .map(|(rarity, count)| format!("{rarity:?} x{count}"))
+            .collect();
+        godot_print!(
+            "pulled {} item(s): [{}], {} chance(s) left",
+            result.len(),
+            counts.join(", "),
+            self.chances
+        );
+    }
+
+    /// Register a `FuncRef` that may override the rarity of upcoming pulls, pass `null` to remove it.
+    ///
+    /// The function is called once per pull with a Dictionary describing the batch
+    /// (`index`, `batch_size`, `pity_count`, `hard_pity_count`) and should return a `Rarity`
+    /// to force that outcome, or `null` to roll normally. This is meant for scripted moments such as
+    /// the tutorial's guaranteed first SSR, so it's refused outside debug builds unless
+    /// `allow_rarity_resolver` is set.
+    #[method]
+    fn set_rarity_resolver(&mut self, resolver: Option<Ref<FuncRef>>) {
+        if resolver.is_some() && !self.allow_rarity_resolver && !self._debug_build {
+            godot_warn!("rarity resolver rejected: `allow_rarity_resolver` is not set");
+            return;
+        }
+        self.rarity_resolver = resolver;
+    }
+
+    /// Ask the registered rarity resolver (if any) for a forced rarity.
+    fn resolved_rarity(&self, index: u32, batch_size: u32) -> Option<Rarity> {
+        let resolver = self.rarity_resolver.as_ref()?;
+        // SAFETY: the resolver is only ever called from the main thread during `pull`.
+        let resolver = unsafe { resolver.assume_safe() };
+        if !resolver.is_valid() {
+            return None;
+        }
+
+        let context = Dictionary::new();
+        context.insert("index", index);
+        context.insert("batch_size", batch_size);
+        context.insert("pity_count", self._pity_accu);
+        context.insert("hard_pity_count", self._hard_pity_accu);
+        resolver
+            .call_func(&[context.owned_to_variant()])
+            .to::<Rarity>()
+    }
+
+    fn set_scripted_results(&mut self, items: Vec<GachaItem>) {
+        self.adopt_shared();
+        self._configured_scripted_results = items.clone();
+        self.scripted_results = items;
+        self.publish_shared();
+    }
+
+    /// Issue a one-time confirmation token required by `reset_profile`.
+    ///
+    /// Any previously issued token is invalidated.
+    #[method]
+    fn request_reset_token(&mut self) -> String {
+        self.adopt_shared();
+        let token = new_token();
+        self._reset_token = Some(token.clone());
+        self.publish_shared();
+        token
+    }
+
+    /// Wipe the gacha state for a re-roll, returns `false` if nothing was reset.
+    ///
+    /// `token` must match the one returned by the latest `request_reset_token` call, and is
+    /// consumed either way. `keep` lists state that should survive the reset:
+    /// `"chances"` for the remaining pulls, `"pity"` for the pity counters.
+    ///
+    /// Everything else starts over: the `scripted_results` are handed out again, boxes are
+    /// refilled and the spend confirmation is cleared. Only the daily and monthly pull counts
+    /// survive, so re-rolling can't get around the spending limits.
+    #[method]
+    fn reset_profile(&mut self, keep: Vec<String>, token: String) -> bool {
+        self.adopt_shared();
+        if self._reset_token.take().as_deref() != Some(token.as_str()) {
+            self.publish_shared();
+            godot_warn!("profile reset rejected: invalid or expired confirmation token");
+            return false;
+        }
+
+        for key in keep
+            .iter()
+            .filter(|k| !RESET_KEEP_KEYS.contains(&k.as_str()))
+        {
+            godot_warn!(
+                "reset_profile ignores unknown keep key \"{key}\", expected one of {}",
+                RESET_KEEP_KEYS.join(", ")
+            );
+        }
+        let keeps = |key: &str| keep.iter().any(|k| k == key);
+        self.scripted_results = self._configured_scripted_results.clone();
+        self._session_pulls = 0;
+        self._spend_token = None;
+        self.item_sampler.as_sampler().reset();
+        for banner in self.banners.values_mut() {
+            banner.item_sampler.as_sampler().reset();
+        }
+        if !keeps("chances") {
+            self.chances = 0;
+        }
+        if !keeps("pity") {
+            self._pity_accu = 0;
+            self._hard_pity_accu = 0;
+            self._featured_guarantee = false;
+            for banner in self.banners.values_mut() {
+                banner.set_counters(Default::default());
+            }
+        }
+        self.publish_shared();
+        true
+    }
+
+    /// Pick an item of the given rarity with the item sampler, returning its index in the pool along with it.
+    ///
+    /// Disabled items are left out of the pick. If every item of the rarity is disabled, the item
+    /// comes from the next lower rarity that has enabled items.
+    fn gacha_by_rarity(
+        &mut self,
+        rarity: Rarity,
+        rng: &mut ChaCha8Rng,
+    ) -> Result<(usize, GachaItem)> {
+        if !self.data.contains_key(&rarity) {
+            return Err(GachaError::InvalidRarity(format!("{rarity:?}")));
+        }
+        let (rarity, enabled) = self
             .data
-            .get(&rarity)
-            .ok_or_else(|| GachaError::InvalidRarity(format!("{rarity:?}")))?;
-        let res = poll
-            .choose(rng)
-            .ok_or_else(|| GachaError::RarityWithNoData(format!("{rarity:?}")))?
-            .clone();
+            .range(rarity..)
+            .map(|(rarity, pool)| (*rarity, self.enabled_items(pool)))
+            .find(|(_, enabled)| !enabled.is_empty())
+            .ok_or_else(|| GachaError::RarityWithNoData(format!("{rarity:?}")))?;
+        let enabled = if rarity == Rarity::SSR {
+            self.featured_split(enabled, rng)
+        } else {
+            enabled
+        };
+        let (indices, poll): (Vec<usize>, Vec<GachaItem>) = enabled.into_iter().unzip();
+        let picked = self
+            .item_sampler
+            .as_sampler()
+            .pick(rarity, &poll, rng)
+            .ok_or_else(|| GachaError::RarityWithNoData(format!("{rarity:?}")))?;
+        let (idx, res) = (indices[picked], poll[picked].clone());
 
         // only update counters when successfully pulled
+        self.record_pull(rarity);
+        Ok((idx, res))
+    }
+
+    /// Narrow the SSR candidates down to the featured or the other items by the 50/50,
+    /// updating the guarantee. Candidates are left alone without both kinds of items.
+    fn featured_split(
+        &mut self,
+        enabled: Vec<(usize, GachaItem)>,
+        rng: &mut ChaCha8Rng,
+    ) -> Vec<(usize, GachaItem)> {
+        // box contents are indices into the rarity's enabled items
+        if matches!(self.item_sampler, ItemSampler::Box(_)) {
+            return enabled;
+        }
+        let (featured, others): (Vec<_>, Vec<_>) = enabled
+            .into_iter()
+            .partition(|(_, item)| self.featured_items.contains(&item.name));
+        if featured.is_empty() || others.is_empty() {
+            return if featured.is_empty() {
+                others
+            } else {
+                featured
+            };
+        }
+        let won = self._featured_guarantee || rng.gen::<f64>() < self.featured_rate;
+        self._featured_guarantee = !won;
+        self.log_roll(|| format!("featured 50/50 {}", if won { "won" } else { "lost" }));
+        if won {
+            featured
+        } else {
+            others
+        }
+    }
+
+    /// Items of `pool` that aren't disabled, along with their index in it.
+    fn enabled_items(&self, pool: &[GachaItem]) -> Vec<(usize, GachaItem)> {
+        pool.iter()
+            .enumerate()
+            .filter(|(_, item)| !self.disabled_items.contains(&item.name))
+            .map(|(idx, item)| (idx, item.clone()))
+            .collect()
+    }
+
+    /// Take the next scripted result out of the queue, counting it as a regular pull.
+    fn next_scripted_result(&mut self) -> Option<GachaItem> {
+        if self.scripted_results.is_empty() {
+            return None;
+        }
+        let item = self.scripted_results.remove(0);
+        self.record_pull(item.rarity);
+        Some(item)
+    }
+
+    /// Consume a chance and update pity counters for a pull of the given rarity.
+    fn record_pull(&mut self, rarity: Rarity) {
         self.chances -= 1;
         match rarity {
             Rarity::SSR => {
@@ -122,55 +1557,211 @@ impl GachaSystem {
                 self._hard_pity_accu += 1;
                 self._pity_accu += 1;
             }
-        }
+        }
+    }
+
+    fn soft_ramp(&self) -> SoftRamp {
+        SoftRamp {
+            start: self.soft_pity_start,
+            step: self.soft_pity_step,
+        }
+    }
+
+    /// Rates for the next pull after the rate modifiers, `None` if unchanged: the soft pity
+    /// ramp, then the configured pity strategy so its guarantees have the last word.
+    fn adjusted_rates(&self) -> Option<Vec<(Rarity, f64)>> {
+        let modifiers: [&dyn PityStrategy; 2] = [&self.soft_ramp(), self.pity_strategy.strategy()];
+        apply_modifiers(&modifiers, self.pity_counters(), &self.rarities)
+    }
+
+    fn pity_counters(&self) -> PityCounters {
+        PityCounters {
+            pity_count: self._pity_accu,
+            hard_pity_count: self._hard_pity_accu,
+            pity: self.pity,
+            hard_pity: self.hard_pity,
+        }
+    }
+}
+
+/// Check that there is something to pull from `data` with `rarities`, `None` if there is.
+pub(crate) fn pool_problem(data: &ItemPools, rarities: &[(Rarity, f64)]) -> Option<String> {
+    if data.is_empty() {
+        return Some("`data` has no item pools".to_string());
+    }
+    if rarities.is_empty() {
+        return Some("`rarities` is empty".to_string());
+    }
+    rarities
+        .iter()
+        .filter(|(_, rate)| *rate > 0.0)
+        .find(|(rarity, _)| data.get(rarity).is_none_or(Vec::is_empty))
+        .map(|(rarity, _)| format!("{rarity:?} has a rate but no items in `data`"))
+}
+
+/// Random one-time token for confirmations.
+fn new_token() -> String {
+    format!("{:016x}", thread_rng().gen::<u64>())
+}
+
+pub(crate) fn to_array<T: ToVariant>(values: &[T]) -> VariantArray {
+    values
+        .iter()
+        .map(ToVariant::to_variant)
+        .collect::<VariantArray<Unique>>()
+        .into_shared()
+}
+
+/// Convert an Array of `GachaItem`s, `path` names the property in errors.
+fn items_from_array(array: &VariantArray, path: &str) -> Result<Vec<GachaItem>> {
+    array
+        .iter()
+        .enumerate()
+        .map(|(idx, item)| {
+            let item = GachaItem::from_variant(&item).map_err(|e| {
+                GachaError::InvalidProperty(
+                    format!("{path}[{idx}]"),
+                    format!("expected a Dictionary with `name` and `rarity`: {e}"),
+                )
+            })?;
+            match item.weight {
+                Some(weight) if !weight.is_finite() || weight < 0.0 => {
+                    Err(GachaError::InvalidProperty(
+                        format!("{path}[{idx}].weight"),
+                        format!("{weight} is not a finite, non-negative number"),
+                    ))
+                }
+                _ => Ok(item),
+            }
+        })
+        .collect()
+}
 
-        Ok(res)
-    }
+/// Convert an Array of `[Rarity, rate]` pairs, `path` names the property in errors.
+pub(crate) fn rates_from_array(array: &VariantArray, path: &str) -> Result<Vec<(Rarity, f64)>> {
+    array
+        .iter()
+        .enumerate()
+        .map(|(idx, pair)| {
+            <(Rarity, f64)>::from_variant(&pair).map_err(|e| {
+                GachaError::InvalidProperty(
+                    format!("{path}[{idx}]"),
+                    format!("expected a [Rarity, float] pair: {e}"),
+                )
+            })
+        })
+        .collect()
+}
 
-    /// Return a Vec of rarities if a pity was hit.
-    ///
-    /// If a soft pity was hit, meaning there's a chance to get SR or SSR,
-    /// But if a hard pity was hit, the next pull will only be SSR;
-    fn pity_rarities_and_rate(&mut self) -> Option<Vec<(Rarity, f64)>> {
-        if self._hard_pity_accu + 1 == self.hard_pity {
-            Some(
-                self.rarities
-                    .iter()
-                    .filter(|(r, _)| *r == Rarity::SSR)
-                    .cloned()
-                    .collect(),
-            )
-        } else if self._pity_accu + 1 == self.pity {
-            Some(
-                self.rarities
-                    .iter()
-                    .filter(|(r, _)| *r == Rarity::SSR || *r == Rarity::SR)
-                    .cloned()
-                    .collect(),
-            )
-        } else {
-            None
+/// `config_hash` input for one banner, one line per setting.
+fn banner_config(
+    id: &str,
+    data: &ItemPools,
+    rarities: &[(Rarity, f64)],
+    sampler: &ItemSampler,
+    (featured, featured_rate): (&[String], f64),
+) -> String {
+    let mut config = format!("banner {} {sampler}\n", hash_text(id));
+    for (rarity, rate) in rarities {
+        config += &format!("rate {} {}\n", hash_rarity(*rarity), hash_float(*rate));
+    }
+    for (rarity, items) in data.iter() {
+        for item in items {
+            let weight = item.weight.map_or("-".to_string(), hash_float);
+            let name = hash_text(&item.name);
+            config += &format!("item {} {name} {weight}\n", hash_rarity(*rarity));
         }
     }
+    config += &format!("featured_rate {}\n", hash_float(featured_rate));
+    for name in featured {
+        config += &format!("featured {}\n", hash_text(name));
+    }
+    config
 }
 
-fn rarity_range(rarities: &[(Rarity, f64)]) -> Vec<(Rarity, Range<f64>)> {
-    let mut hashmap = Vec::new();
-    let mut sum = 0.0;
-    for (rarity, rate) in rarities {
-        let lo = sum;
-        let hi = lo + rate;
-        hashmap.push((*rarity, lo..hi));
-        sum = hi;
+fn hash_rarity(rarity: Rarity) -> &'static str {
+    match rarity {
+        Rarity::SSR => "SSR",
+        Rarity::SR => "SR",
+        Rarity::R => "R",
+        Rarity::N => "N",
+    }
+}
+
+/// The exact bits of `value`, so the hash doesn't depend on how floats are printed.
+fn hash_float(value: f64) -> String {
+    format!("{:016x}", value.to_bits())
+}
+
+/// `text` prefixed with its length, so names can't run into the next field.
+fn hash_text(text: &str) -> String {
+    format!("{}:{text}", text.len())
+}
+
+/// Roll a rarity from the given rates, returning it along with the rolled weight.
+///
+/// Degenerate rates (NaN, infinite, negative, or nothing above zero) are reported as errors
+/// instead of letting `gen_range` panic, and so are positive rates below [`MIN_RATE`], which
+/// would roll with a different chance than configured.
+fn sample_rarity<R: Rng + ?Sized>(
+    rarities: &[(Rarity, f64)],
+    rng: &mut R,
+) -> Result<(Rarity, u128)> {
+    if let Some((rarity, rate)) = rarities
+        .iter()
+        .find(|(_, rate)| !rate.is_finite() || *rate < 0.0 || (*rate > 0.0 && *rate < MIN_RATE))
+    {
+        return Err(GachaError::InvalidRate(format!("{rarity:?}"), *rate));
     }
-    hashmap
+    let weights = rarity_weights(rarities);
+    let total: u128 = weights.iter().map(|(_, weight)| u128::from(*weight)).sum();
+    if total == 0 {
+        return Err(GachaError::NoRarityAvailable);
+    }
+
+    let roll = rng.gen_range(0..total);
+    let rarity = pick_weighted(&weights, roll).ok_or(GachaError::NoRarityAvailable)?;
+    Ok((rarity, roll))
+}
+
+/// Convert rates into integer weights, so that sampling doesn't depend on float range boundaries.
+///
+/// Rates are exact up to [`RATE_SCALE`], so any rate of at least [`MIN_RATE`] keeps a weight.
+fn rarity_weights(rarities: &[(Rarity, f64)]) -> Vec<(Rarity, u64)> {
+    rarities
+        .iter()
+        .map(|(rarity, rate)| (*rarity, (rate * RATE_SCALE).round() as u64))
+        .collect()
+}
+
+/// Pick the first rarity whose cumulative weight exceeds `roll`.
+fn pick_weighted(weights: &[(Rarity, u64)], roll: u128) -> Option<Rarity> {
+    let mut cumulative = 0_u128;
+    weights
+        .iter()
+        .find(|(_, weight)| {
+            cumulative += u128::from(*weight);
+            roll < cumulative
+        })
+        .map(|(rarity, _)| *rarity)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{rarity_range, GachaItem, GachaSystem, Range, Rarity};
+    use super::{
+        pick_weighted, rarity_weights, sample_rarity, GachaItem, GachaSystem, ItemPools, Rarity,
+        API_VERSION, MIN_RATE,
+    };
+    use crate::{
+        banner::{Banner, STANDARD_BANNER},
+        error::GachaError,
+        fairness,
+        profile::shared_counters,
+        state::SavedState,
+    };
     use lazy_static::lazy_static;
-    use std::collections::HashMap;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::collections::{BTreeMap, BTreeSet};
 
     static RARITIES: &[(Rarity, f64)] = &[
         (Rarity::SSR, 0.05),
@@ -180,35 +1771,116 @@ mod tests {
     ];
 
     lazy_static! {
-        static ref DATA: HashMap<Rarity, Vec<GachaItem>> = HashMap::from([
+        static ref DATA: ItemPools = [
             (Rarity::SSR, gacha_items(Rarity::SSR, 2)),
             (Rarity::SR, gacha_items(Rarity::SR, 3)),
             (Rarity::R, gacha_items(Rarity::R, 4)),
             (Rarity::N, gacha_items(Rarity::N, 3)),
-        ]);
+        ]
+        .into_iter()
+        .collect();
     }
 
     fn gacha_items(rarity: Rarity, num: u8) -> Vec<GachaItem> {
         let mut res = vec![];
         for i in 0..num {
             let name = format!("{rarity:?}-{i}");
-            res.push(GachaItem { name, rarity });
+            res.push(GachaItem {
+                name,
+                rarity,
+                weight: None,
+            });
         }
         res
     }
 
     #[test]
-    fn ranges() {
-        let precision_round = |x: f64, mul: f64| -> f64 { (x * mul).round() / mul };
+    fn pools_iterate_in_rarity_order() {
+        let pools: ItemPools = [Rarity::N, Rarity::SR, Rarity::R, Rarity::SSR]
+            .into_iter()
+            .map(|rarity| (rarity, gacha_items(rarity, 1)))
+            .collect();
+        let order: Vec<Rarity> = pools.keys().copied().collect();
+        assert_eq!(order, [Rarity::SSR, Rarity::SR, Rarity::R, Rarity::N]);
 
-        let actural: Vec<Range<f64>> = rarity_range(&RARITIES)
-            .iter()
-            .map(|(_, rg)| precision_round(rg.start, 100.0)..precision_round(rg.end, 100.0))
+        let names: Vec<&str> = DATA
+            .values()
+            .flatten()
+            .map(|item| item.name.as_str())
             .collect();
-        let expected = vec![0.00..0.05, 0.05..0.40, 0.40..0.80, 0.80..1.00];
+        assert_eq!(names.first(), Some(&"SSR-0"));
+        assert_eq!(names.last(), Some(&"N-2"));
+    }
+
+    #[test]
+    fn weights() {
+        let actural: Vec<u64> = rarity_weights(RARITIES).iter().map(|(_, w)| *w).collect();
+        let expected = vec![50_000_000, 350_000_000, 400_000_000, 200_000_000];
         assert_eq!(actural, expected);
     }
 
+    #[test]
+    fn weight_boundaries() {
+        let weights = [(Rarity::SSR, 1), (Rarity::SR, 0), (Rarity::N, 2)];
+        assert_eq!(pick_weighted(&weights, 0), Some(Rarity::SSR));
+        // zero weight is never picked, even right at its boundary
+        assert_eq!(pick_weighted(&weights, 1), Some(Rarity::N));
+        assert_eq!(pick_weighted(&weights, 2), Some(Rarity::N));
+        assert_eq!(pick_weighted(&weights, 3), None);
+    }
+
+    #[test]
+    fn tiny_rate_unbiased() {
+        const DRAWS: u32 = 1_000_000;
+        let mut rng = StdRng::seed_from_u64(421);
+        let rates = [(Rarity::SSR, 0.001), (Rarity::N, 0.999)];
+
+        let hits = (0..DRAWS)
+            .filter(|_| sample_rarity(&rates, &mut rng).unwrap().0 == Rarity::SSR)
+            .count() as f64;
+        // expected 1000 hits with a standard deviation of ~31.6, allow 5 sigma
+        let expected = f64::from(DRAWS) * 0.001;
+        assert!(
+            (hits - expected).abs() < 160.0,
+            "got {hits} SSR in {DRAWS} draws"
+        );
+    }
+
+    #[test]
+    fn degenerate_rates() {
+        let mut rng = rand::thread_rng();
+        let sample = |rarities: &[(Rarity, f64)]| sample_rarity(rarities, &mut rand::thread_rng());
+
+        assert!(matches!(
+            sample(&[(Rarity::SSR, f64::NAN), (Rarity::N, 1.0)]),
+            Err(GachaError::InvalidRate(_, _))
+        ));
+        assert!(matches!(
+            sample(&[(Rarity::SSR, f64::INFINITY)]),
+            Err(GachaError::InvalidRate(_, _))
+        ));
+        assert!(matches!(
+            sample(&[(Rarity::SSR, -0.1), (Rarity::N, 1.0)]),
+            Err(GachaError::InvalidRate(_, _))
+        ));
+        assert!(matches!(
+            sample(&[(Rarity::SSR, 0.0), (Rarity::N, 0.0)]),
+            Err(GachaError::NoRarityAvailable)
+        ));
+        assert!(matches!(sample(&[]), Err(GachaError::NoRarityAvailable)));
+
+        // rates finer than the weights can hold would be rolled with a larger chance
+        assert!(matches!(
+            sample(&[(Rarity::SSR, 1e-12), (Rarity::N, 1.0)]),
+            Err(GachaError::InvalidRate(_, _))
+        ));
+        for _ in 0..100 {
+            let (rarity, _) =
+                sample_rarity(&[(Rarity::SSR, 0.0), (Rarity::R, MIN_RATE)], &mut rng).unwrap();
+            assert_eq!(rarity, Rarity::R);
+        }
+    }
+
     #[test]
     fn pull() {
         let mut gacha = GachaSystem {
@@ -217,11 +1889,11 @@ mod tests {
             data: DATA.clone(),
             ..Default::default()
         };
-        let res = gacha.pull(1);
+        let res = gacha.pull_items(1);
         println!("1 pull: {:?}", res);
         assert_eq!(res.len(), 1);
 
-        let ten_poll_res = gacha.pull(10);
+        let ten_poll_res = gacha.pull_items(10);
         println!("10 pull: {:?}", ten_poll_res);
         assert_eq!(ten_poll_res.len(), 10);
     }
@@ -234,7 +1906,7 @@ mod tests {
             data: DATA.clone(),
             ..Default::default()
         };
-        let res = gacha.pull(1000);
+        let res = gacha.pull_items(1000);
         println!("all pull: {:?}", res);
         assert_eq!(res.len(), 8);
     }
@@ -255,8 +1927,8 @@ mod tests {
             ..Default::default()
         };
 
-        let has_sr = gacha.pull(1);
-        assert_eq!(has_sr.get(0).map(|gd| gd.rarity), Some(Rarity::SR));
+        let has_sr = gacha.pull_items(1);
+        assert_eq!(has_sr.first().map(|gd| gd.rarity), Some(Rarity::SR));
     }
 
     #[test]
@@ -275,8 +1947,8 @@ mod tests {
             ..Default::default()
         };
 
-        let has_sr = gacha.pull(1);
-        assert_eq!(has_sr.get(0).map(|gd| gd.rarity), Some(Rarity::SSR));
+        let has_sr = gacha.pull_items(1);
+        assert_eq!(has_sr.first().map(|gd| gd.rarity), Some(Rarity::SSR));
     }
 
     #[test]
@@ -295,7 +1967,568 @@ mod tests {
             ..Default::default()
         };
 
-        let has_sr = gacha.pull(1);
-        assert_eq!(has_sr.get(0).map(|gd| gd.rarity), Some(Rarity::SSR));
+        let has_sr = gacha.pull_items(1);
+        assert_eq!(has_sr.first().map(|gd| gd.rarity), Some(Rarity::SSR));
+    }
+
+    #[test]
+    fn disabled_items() {
+        let mut gacha = GachaSystem {
+            chances: 50,
+            rarities: vec![(Rarity::SSR, 0.5), (Rarity::SR, 0.5)],
+            data: DATA.clone(),
+            disabled_items: vec!["SSR-0".into(), "SR-0".into(), "SR-1".into(), "SR-2".into()],
+            item_sampler: "box".parse().unwrap(),
+            audit_rolls: true,
+            ..Default::default()
+        };
+
+        let items = gacha.pull_items(50);
+        assert_eq!(items.len(), 50);
+        for item in &items {
+            assert!(!gacha.disabled_items.contains(&item.name), "{item:?}");
+            // SR has nothing enabled left, so those pulls fall back to R
+            assert!(item.name == "SSR-1" || item.rarity == Rarity::R, "{item:?}");
+        }
+        assert!(items.iter().any(|item| item.rarity == Rarity::R));
+        for audit in &gacha._last_audit {
+            let pool = &DATA[&audit.rarity];
+            assert_eq!(pool[audit.item_index.unwrap()].name, audit.item);
+        }
+    }
+
+    #[test]
+    fn roll_audit() {
+        let mut gacha = GachaSystem {
+            chances: 10,
+            rarities: RARITIES.to_owned(),
+            pity: 10,
+            hard_pity: 50,
+            data: DATA.clone(),
+            scripted_results: gacha_items(Rarity::SSR, 1),
+            ..Default::default()
+        };
+        gacha.pull_items(2);
+        assert!(gacha.get_last_pull_audit().is_empty());
+
+        gacha.audit_rolls = true;
+        let res = gacha.pull_items(3);
+        let audit = gacha.get_last_pull_audit();
+        assert_eq!(audit.len(), 3);
+        for (record, item) in audit.iter().zip(&res) {
+            assert_eq!(record.source, "roll");
+            assert_eq!(record.item, item.name);
+            assert_eq!(record.candidates, super::rarity_weights(RARITIES));
+            let total: u64 = record.candidates.iter().map(|(_, w)| w).sum();
+            assert!(record.roll.unwrap() < total);
+            assert_eq!(
+                DATA[&item.rarity][record.item_index.unwrap()].name,
+                item.name
+            );
+        }
+    }
+
+    #[test]
+    fn replay_recording() {
+        let mut gacha = GachaSystem {
+            chances: 100,
+            rarities: RARITIES.to_owned(),
+            pity: 10,
+            hard_pity: 50,
+            data: DATA.clone(),
+            upgrade_tease_chance: 0.5,
+            item_sampler: "box".parse().unwrap(),
+            ..Default::default()
+        };
+        gacha.pull_items(7);
+        gacha.start_recording();
+        gacha.pull_banner(STANDARD_BANNER, 10, true);
+        gacha.pull_banner(STANDARD_BANNER, 3, false);
+        gacha.pull_banner(STANDARD_BANNER, 10, true);
+        let recording = gacha.stop_recording().unwrap();
+        assert_eq!(recording.commands.len(), 3);
+        assert!(gacha.stop_recording().is_none());
+
+        gacha.pull_items(20);
+        let report = gacha.replay(&recording);
+        assert!(report.config_matches);
+        assert_eq!(report.diverged_at, None);
+        assert_eq!(gacha.chances, 100 - 7 - 23);
+
+        let mut tampered = recording.clone();
+        tampered.commands[1].items[0] = "not pulled".into();
+        let report = gacha.replay(&tampered);
+        assert_eq!(report.diverged_at, Some(1));
+        assert_eq!(report.expected.unwrap().items[0], "not pulled");
+
+        gacha.hard_pity = 60;
+        assert!(!gacha.replay(&recording).config_matches);
+    }
+
+    #[test]
+    fn verifiable_pulls() {
+        let verifiable = || GachaSystem {
+            chances: 100,
+            rarities: RARITIES.to_owned(),
+            data: DATA.clone(),
+            verifiable_pulls: true,
+            ..Default::default()
+        };
+        let names = |items: Vec<GachaItem>| -> Vec<String> {
+            items.into_iter().map(|item| item.name).collect()
+        };
+        let mut gacha = verifiable();
+        let published = gacha.get_fairness_commitment().unwrap();
+        assert_eq!(gacha.get_fairness_commitment().unwrap(), published);
+        let pulled = names(gacha.pull_items(10));
+
+        let proof = gacha.get_last_fairness_proof().unwrap();
+        assert_eq!(proof.commitment, published);
+        assert_eq!(fairness::commitment(&proof.seed), published);
+        assert_ne!(gacha.get_fairness_commitment().unwrap(), published);
+
+        // the revealed seed reproduces the pull
+        let mut check = verifiable();
+        check._fairness_seed = Some(u64::from_str_radix(&proof.seed, 16).unwrap());
+        assert_eq!(names(check.pull_items(10)), pulled);
+
+        assert!(proof.unverified.is_empty());
+
+        // neither starting a recording nor replaying one breaks a published commitment
+        let published = gacha.get_fairness_commitment().unwrap();
+        gacha.start_recording();
+        gacha.pull_banner(STANDARD_BANNER, 1, false);
+        let recording = gacha.stop_recording().unwrap();
+        assert_eq!(
+            gacha.get_last_fairness_proof().unwrap().commitment,
+            published
+        );
+        let published = gacha.get_fairness_commitment().unwrap();
+        assert_eq!(recording.commands.len(), 1);
+        assert_eq!(gacha.replay(&recording).diverged_at, None);
+        assert_eq!(gacha.get_fairness_commitment().unwrap(), published);
+
+        // the seed doesn't decide scripted results, so the proof leaves them out
+        gacha.scripted_results = gacha_items(Rarity::SSR, 1);
+        gacha.pull_items(2);
+        assert_eq!(gacha.get_last_fairness_proof().unwrap().unverified, [0]);
+
+        gacha.verifiable_pulls = false;
+        assert!(gacha.get_fairness_commitment().is_none());
+    }
+
+    #[test]
+    fn facade_api_version() {
+        let facade = include_str!("../../frontend/lib/gacha_system.gd");
+        let expected = facade
+            .lines()
+            .find_map(|line| line.strip_prefix("const API_VERSION := "))
+            .expect("facade declares API_VERSION")
+            .trim_matches('"');
+        let major = |version: &str| version.split('.').next().unwrap().to_string();
+        let minor = |version: &str| version.split('.').nth(1).unwrap().parse::<u32>().unwrap();
+        assert_eq!(major(expected), major(API_VERSION));
+        assert!(minor(expected) <= minor(API_VERSION));
+    }
+
+    #[test]
+    fn facade_wraps_every_method() {
+        // identifier starting `text`, e.g. `pull` out of `pull(banner_id, num)`
+        let name = |text: &str| -> String {
+            text.chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_')
+                .collect()
+        };
+        let facade: BTreeSet<String> = include_str!("../../frontend/lib/gacha_system.gd")
+            .split("native.")
+            .skip(1)
+            .map(name)
+            .filter(|method| method != "has_method")
+            .collect();
+        let mut native = BTreeSet::new();
+        let mut lines = include_str!("gacha_core.rs").lines();
+        while let Some(line) = lines.next() {
+            if line.trim() != "#[method]" {
+                continue;
+            }
+            let method = lines
+                .find_map(|line| line.trim().strip_prefix("fn "))
+                .map(name)
+                .unwrap();
+            // engine callbacks aren't for scripts to call
+            if !method.starts_with('_') {
+                native.insert(method);
+            }
+        }
+        assert_eq!(
+            facade, native,
+            "frontend/lib/gacha_system.gd is out of date"
+        );
+    }
+
+    #[test]
+    fn configuration_problems() {
+        let gacha = GachaSystem::default();
+        assert!(gacha.configuration_problem().is_some());
+
+        let mut gacha = GachaSystem {
+            rarities: RARITIES.to_owned(),
+            data: DATA.clone(),
+            ..Default::default()
+        };
+        assert_eq!(gacha.configuration_problem(), None);
+
+        gacha.data = DATA
+            .iter()
+            .filter(|(rarity, _)| **rarity != Rarity::SR)
+            .map(|(rarity, items)| (*rarity, items.clone()))
+            .collect();
+        assert!(gacha.configuration_problem().is_some());
+        // a rarity without items is fine as long as it can't be rolled
+        gacha.rarities.retain(|(rarity, _)| *rarity != Rarity::SR);
+        assert_eq!(gacha.configuration_problem(), None);
+    }
+
+    #[test]
+    fn scripted_results_first() {
+        let starter = GachaItem {
+            name: "starter".into(),
+            rarity: Rarity::SSR,
+            weight: None,
+        };
+        let mut gacha = GachaSystem {
+            chances: 10,
+            rarities: RARITIES.to_owned(),
+            pity: 10,
+            hard_pity: 50,
+            data: DATA.clone(),
+            scripted_results: vec![starter],
+            ..Default::default()
+        };
+
+        let res = gacha.pull_items(3);
+        assert_eq!(res.len(), 3);
+        assert_eq!(res[0].name, "starter");
+        assert!(res[1..].iter().all(|item| item.name != "starter"));
+        assert!(gacha.scripted_results.is_empty());
+        assert_eq!(gacha.chances, 7);
+    }
+
+    #[test]
+    fn spend_confirmation() {
+        let mut gacha = GachaSystem {
+            confirm_spend_after: 20,
+            _session_pulls: 19,
+            ..Default::default()
+        };
+        assert_eq!(gacha.spend_confirmation(), None);
+
+        gacha._session_pulls = 20;
+        let token = gacha.spend_confirmation().unwrap();
+        assert_eq!(gacha.spend_confirmation(), Some(token.clone()));
+        assert!(!gacha.confirm_spend("not-a-token".into()));
+        assert_eq!(gacha.pending_spend_confirmation(), Some(token.clone()));
+
+        assert!(gacha.confirm_spend(token.clone()));
+        assert_eq!(gacha.spend_confirmation(), None);
+        assert!(!gacha.confirm_spend(token));
+    }
+
+    #[test]
+    fn reset_profile_needs_token() {
+        let mut gacha = GachaSystem {
+            chances: 10,
+            _pity_accu: 3,
+            _hard_pity_accu: 5,
+            ..Default::default()
+        };
+
+        assert!(!gacha.reset_profile(vec![], "not-a-token".into()));
+        assert_eq!(gacha.chances, 10);
+
+        let token = gacha.request_reset_token();
+        assert!(gacha.reset_profile(vec!["chances".into()], token.clone()));
+        assert_eq!(gacha.chances, 10);
+        assert_eq!((gacha._pity_accu, gacha._hard_pity_accu), (0, 0));
+        // tokens are single use
+        assert!(!gacha.reset_profile(vec![], token));
+    }
+
+    #[test]
+    fn reroll_after_tutorial() {
+        let mut gacha = GachaSystem {
+            chances: 20,
+            rarities: RARITIES.to_owned(),
+            data: DATA.clone(),
+            item_sampler: "box".parse().unwrap(),
+            confirm_spend_after: 5,
+            ..Default::default()
+        };
+        let starter = gacha_items(Rarity::SSR, 1);
+        gacha.set_scripted_results(starter.clone());
+        assert_eq!(gacha.pull_items(1), starter);
+        gacha.pull_items(5);
+        gacha._session_pulls = 6;
+        assert!(gacha.spend_confirmation().is_some());
+        assert!(!gacha.item_sampler.box_state().is_empty());
+
+        let token = gacha.request_reset_token();
+        assert!(gacha.reset_profile(vec!["chances".into(), "unknown".into()], token));
+        assert_eq!(gacha.scripted_results, starter);
+        assert!(gacha.item_sampler.box_state().is_empty());
+        assert_eq!(gacha._session_pulls, 0);
+        assert_eq!(gacha.pending_spend_confirmation(), None);
+        // the re-rolled account gets the tutorial starter again
+        assert_eq!(gacha.pull_items(1), starter);
+    }
+
+    #[test]
+    fn shared_profile_counters() {
+        let shared = shared_counters("shared_profile_counters");
+        let mut first = GachaSystem {
+            chances: 10,
+            rarities: RARITIES.to_owned(),
+            data: DATA.clone(),
+            _shared: Some(shared.clone()),
+            ..Default::default()
+        };
+        first.publish_shared();
+        let mut second = GachaSystem {
+            _shared: Some(shared),
+            ..Default::default()
+        };
+        second.adopt_shared();
+        assert_eq!(second.chances, 10);
+
+        first.pull_items(4);
+        first.publish_shared();
+        second.adopt_shared();
+        assert_eq!(second.profile_counters(), first.profile_counters());
+        assert_eq!(second.chances, 6);
+    }
+
+    #[test]
+    fn shared_scripted_results_and_tokens() {
+        let shared = shared_counters("shared_scripted_results_and_tokens");
+        let starter = gacha_items(Rarity::SSR, 1);
+        let node = |scripted_results| GachaSystem {
+            chances: 10,
+            rarities: vec![(Rarity::N, 1.0)],
+            data: DATA.clone(),
+            scripted_results,
+            confirm_spend_after: 5,
+            _shared: Some(shared.clone()),
+            ..Default::default()
+        };
+        let mut first = node(starter.clone());
+        first.publish_shared();
+        // the second scene's node is configured with the same tutorial results
+        let mut second = node(starter.clone());
+        second.adopt_shared();
+
+        assert_eq!(first.pull_items(1), starter);
+        first.publish_shared();
+        second.adopt_shared();
+        assert!(second
+            .pull_items(1)
+            .iter()
+            .all(|item| item.rarity == Rarity::N));
+
+        let token = first.request_reset_token();
+        assert!(second.reset_profile(vec!["chances".into()], token.clone()));
+        assert!(!first.reset_profile(vec![], token));
+
+        first._session_pulls = 5;
+        let token = first.spend_confirmation().unwrap();
+        first.publish_shared();
+        assert_eq!(second.pending_spend_confirmation(), Some(token.clone()));
+        assert!(second.confirm_spend(token.clone()));
+        assert!(!first.confirm_spend(token));
+    }
+
+    #[test]
+    fn broadcasts() {
+        let mut gacha = GachaSystem {
+            broadcast: vec!["SSR".into(), "R-2".into()],
+            player_name: "Alice".into(),
+            ..Default::default()
+        };
+        let items: Vec<GachaItem> = DATA.values().flatten().cloned().collect();
+        let names: Vec<&str> = gacha
+            .broadcasts(&items)
+            .iter()
+            .map(|item| item.name.as_str())
+            .collect();
+        assert_eq!(names, ["SSR-0", "SSR-1", "R-2"]);
+
+        assert_eq!(gacha.broadcast_name(), "Alice");
+        gacha.broadcast_anonymize = true;
+        assert_eq!(gacha.broadcast_name(), "A****");
+        gacha.player_name = String::new();
+        assert_eq!(gacha.broadcast_name(), "");
+    }
+
+    #[test]
+    fn banners() {
+        let limited = Banner {
+            data: [(Rarity::SSR, gacha_items(Rarity::SSR, 1))]
+                .into_iter()
+                .collect(),
+            rarities: vec![(Rarity::SSR, 1.0)],
+            item_sampler: "box".parse().unwrap(),
+            ..Default::default()
+        };
+        let mut gacha = GachaSystem {
+            chances: 100,
+            rarities: vec![(Rarity::N, 1.0)],
+            data: DATA.clone(),
+            banners: BTreeMap::from([("limited".to_string(), limited)]),
+            ..Default::default()
+        };
+        assert_eq!(gacha.list_banners(), [STANDARD_BANNER, "limited"]);
+
+        gacha.start_recording();
+        let (items, _) = gacha.pull_banner("limited", 3, false).unwrap();
+        assert!(items.iter().all(|item| item.name == "SSR-0"));
+        let (items, _) = gacha.pull_banner(STANDARD_BANNER, 4, true).unwrap();
+        assert!(items.iter().all(|item| item.rarity == Rarity::N));
+        assert!(gacha.pull_banner("weapon", 1, false).is_none());
+        // each banner counts its own pity, chances are shared
+        assert_eq!((gacha._pity_accu, gacha._hard_pity_accu), (4, 4));
+        assert_eq!(gacha.banners["limited"].hard_pity_count, 0);
+        assert_eq!(gacha.rarities, [(Rarity::N, 1.0)]);
+        assert_eq!(gacha.chances, 93);
+
+        let recording = gacha.stop_recording().unwrap();
+        assert_eq!(recording.commands[0].banner, "limited");
+        let report = gacha.replay(&recording);
+        assert_eq!(report.diverged_at, None);
+    }
+
+    #[test]
+    fn featured_guarantee() {
+        let rate_up = |featured_rate| GachaSystem {
+            chances: 100,
+            rarities: vec![(Rarity::SSR, 1.0)],
+            data: DATA.clone(),
+            featured_items: vec!["SSR-0".into()],
+            featured_rate,
+            ..Default::default()
+        };
+        let names = |items: Vec<GachaItem>| -> Vec<String> {
+            items.into_iter().map(|item| item.name).collect()
+        };
+
+        // every 50/50 is lost, so every other SSR is the guaranteed featured one
+        let mut gacha = rate_up(0.0);
+        assert_eq!(
+            names(gacha.pull_items(4)),
+            ["SSR-1", "SSR-0", "SSR-1", "SSR-0"]
+        );
+        gacha.pull_items(1);
+        assert!(gacha._featured_guarantee);
+
+        let mut gacha = rate_up(1.0);
+        assert_eq!(names(gacha.pull_items(3)), ["SSR-0", "SSR-0", "SSR-0"]);
+        assert!(!gacha._featured_guarantee);
+
+        // a disabled featured item can't be guaranteed
+        let mut gacha = rate_up(0.0);
+        gacha.disabled_items = vec!["SSR-0".into()];
+        assert_eq!(names(gacha.pull_items(3)), ["SSR-1", "SSR-1", "SSR-1"]);
+        assert!(!gacha._featured_guarantee);
+    }
+
+    #[test]
+    fn hard_pity_survives_fallback() {
+        let mut gacha = GachaSystem {
+            chances: 10,
+            rarities: vec![(Rarity::SSR, 1e-9), (Rarity::N, 1.0)],
+            hard_pity: 3,
+            data: DATA.clone(),
+            disabled_items: vec!["SSR-0".into(), "SSR-1".into()],
+            ..Default::default()
+        };
+        // the guaranteed SSR falls back to an SR while every SSR is disabled
+        let items = gacha.pull_items(3);
+        assert_eq!(items[2].rarity, Rarity::SR);
+        assert_eq!(gacha._hard_pity_accu, 3);
+
+        gacha.disabled_items.clear();
+        let items = gacha.pull_items(1);
+        assert_eq!(items[0].rarity, Rarity::SSR);
+        assert_eq!(gacha._hard_pity_accu, 0);
+    }
+
+    #[test]
+    fn config_hash_is_stable() {
+        let node = || GachaSystem {
+            rarities: RARITIES.to_owned(),
+            data: DATA.clone(),
+            pity: 10,
+            hard_pity: 50,
+            ..Default::default()
+        };
+        let gacha = node();
+        // saves only restore box contents while this matches, it must not change between builds
+        let hash = gacha.config_hash();
+        assert_eq!(
+            hash,
+            "25e4351cae442595162c552f68abf144a798e15cffb5899967d1e9b091cfb5d3"
+        );
+
+        // only settings that decide the pulled items count
+        let presentation = GachaSystem {
+            upgrade_tease_chance: 0.5,
+            verifiable_pulls: true,
+            ..node()
+        };
+        assert_eq!(presentation.config_hash(), hash);
+        let banner = Banner {
+            data: DATA.clone(),
+            rarities: RARITIES.to_owned(),
+            ..Default::default()
+        };
+        let mut banners = GachaSystem {
+            banners: BTreeMap::from([("limited".to_string(), banner)]),
+            ..node()
+        };
+        let with_banner = banners.config_hash();
+        assert_ne!(with_banner, hash);
+        banners.banners.get_mut("limited").unwrap().item_sampler = "box".parse().unwrap();
+        assert_ne!(banners.config_hash(), with_banner);
+    }
+
+    #[test]
+    fn saved_state_roundtrip() {
+        let limited = Banner {
+            data: DATA.clone(),
+            rarities: RARITIES.to_owned(),
+            ..Default::default()
+        };
+        let node = || GachaSystem {
+            chances: 50,
+            rarities: RARITIES.to_owned(),
+            data: DATA.clone(),
+            item_sampler: "box".parse().unwrap(),
+            banners: BTreeMap::from([("limited".to_string(), limited.clone())]),
+            ..Default::default()
+        };
+        let mut played = node();
+        played.pull_items(7);
+        played.pull_banner("limited", 5, false);
+        played._featured_guarantee = true;
+        let saved = played.saved_state();
+
+        let mut restored = node();
+        restored.restore_state(saved.clone());
+        assert_eq!(restored.profile_counters(), played.profile_counters());
+        assert_eq!(restored.saved_state(), saved);
+
+        // keys missing from a save keep their current values
+        let mut untouched = node();
+        untouched.restore_state(SavedState::default());
+        assert_eq!(untouched.chances, 50);
+        assert_eq!(untouched.saved_state(), node().saved_state());
     }
 }
@@ -1,8 +1,38 @@
+mod banner;
+mod disclosure;
+mod encounter;
 mod error;
+mod events;
+mod fairness;
+mod flags;
 mod gacha_core;
+mod limits;
+mod number_format;
+mod panic_hook;
+mod pathfinding;
+mod pity;
+mod procgen;
+mod profile;
+mod recording;
+mod reveal;
+mod rng;
+mod sampler;
+mod settings;
+mod state;
+mod storage;
+mod tutorial;
 
+use encounter::EncounterTables;
+use events::RandomEvents;
+use flags::GameFlags;
 use gacha_core::GachaSystem;
 use gdnative::prelude::*;
+use number_format::NumberFormat;
+use pathfinding::GridPathfinder;
+use procgen::ProcGen;
+use settings::Settings;
+use storage::StorageReport;
+use tutorial::TutorialTracker;
 
 #[derive(NativeClass)]
 #[inherit(Node)]
@@ -21,8 +51,18 @@ impl HelloWorld {
 }
 
 fn init(handle: InitHandle) {
+    panic_hook::install();
     handle.add_class::<HelloWorld>();
     handle.add_class::<GachaSystem>();
+    handle.add_class::<GameFlags>();
+    handle.add_class::<ProcGen>();
+    handle.add_class::<GridPathfinder>();
+    handle.add_class::<EncounterTables>();
+    handle.add_class::<RandomEvents>();
+    handle.add_class::<TutorialTracker>();
+    handle.add_class::<Settings>();
+    handle.add_class::<NumberFormat>();
+    handle.add_class::<StorageReport>();
 }
 
 godot_init!(init);
@@ -0,0 +1,142 @@
+use std::fmt;
+
+use gdnative::prelude::*;
+
+const SECS_PER_DAY: u64 = 86_400;
+
+/// Period a pull limit applies to, days and months are counted in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitPeriod {
+    Daily,
+    Monthly,
+}
+
+impl fmt::Display for LimitPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LimitPeriod::Daily => "daily",
+            LimitPeriod::Monthly => "monthly",
+        })
+    }
+}
+
+/// Pulls made in the current day and month, for enforcing spending limits.
+#[derive(Debug, Clone, Default, PartialEq, ToVariant, FromVariant)]
+pub struct SpendTracker {
+    day: u64,
+    month: u64,
+    pulls_today: u32,
+    pulls_this_month: u32,
+}
+
+impl SpendTracker {
+    /// How many of `num` pulls are allowed at unix time `now`, along with the limit that
+    /// cut them short if any. A limit of `0` means unlimited.
+    pub fn allowance(
+        &mut self,
+        now: u64,
+        daily_limit: u32,
+        monthly_limit: u32,
+        num: u32,
+    ) -> (u32, Option<(LimitPeriod, u32)>) {
+        self.roll_over(now);
+        let limits = [
+            (LimitPeriod::Daily, daily_limit, self.pulls_today),
+            (LimitPeriod::Monthly, monthly_limit, self.pulls_this_month),
+        ];
+        limits
+            .into_iter()
+            .filter(|(_, limit, _)| *limit > 0)
+            .map(|(period, limit, used)| (limit.saturating_sub(used), period, limit))
+            .filter(|(remaining, _, _)| *remaining < num)
+            .min_by_key(|(remaining, _, _)| *remaining)
+            .map_or((num, None), |(remaining, period, limit)| {
+                (remaining, Some((period, limit)))
+            })
+    }
+
+    /// Count `pulls` made at unix time `now` towards the limits.
+    pub fn record(&mut self, now: u64, pulls: u32) {
+        self.roll_over(now);
+        self.pulls_today += pulls;
+        self.pulls_this_month += pulls;
+    }
+
+    fn roll_over(&mut self, now: u64) {
+        let day = now / SECS_PER_DAY;
+        if day != self.day {
+            self.day = day;
+            self.pulls_today = 0;
+        }
+        let month = month_index(day);
+        if month != self.month {
+            self.month = month;
+            self.pulls_this_month = 0;
+        }
+    }
+}
+
+/// Months since year 0 of the given day since the unix epoch, in the proleptic Gregorian calendar.
+fn month_index(days: u64) -> u64 {
+    // shift the epoch to 0000-03-01 so leap days end the year, see Howard Hinnant's `civil_from_days`
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // 0 = March
+    let month = (5 * day_of_year + 2) / 153;
+    let year = year_of_era + era * 400 + u64::from(month >= 10);
+    year * 12 + (month + 2) % 12
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{month_index, LimitPeriod, SpendTracker, SECS_PER_DAY};
+
+    #[test]
+    fn months() {
+        // 1970-01-01, 2024-02-29, 2024-03-01, 2024-12-31, 2025-01-01
+        assert_eq!(month_index(0), 1970 * 12);
+        assert_eq!(month_index(19_782), 2024 * 12 + 1);
+        assert_eq!(month_index(19_783), 2024 * 12 + 2);
+        assert_eq!(month_index(20_088), 2024 * 12 + 11);
+        assert_eq!(month_index(20_089), 2025 * 12);
+    }
+
+    #[test]
+    fn limits() {
+        // 2024-03-01
+        let day = 19_783 * SECS_PER_DAY;
+        let mut tracker = SpendTracker::default();
+        assert_eq!(tracker.allowance(day, 0, 0, 10), (10, None));
+        assert_eq!(tracker.allowance(day, 20, 25, 10), (10, None));
+        tracker.record(day, 10);
+        tracker.record(day + 60, 5);
+        assert_eq!(
+            tracker.allowance(day + 120, 20, 25, 10),
+            (5, Some((LimitPeriod::Daily, 20)))
+        );
+        tracker.record(day + 120, 5);
+        assert_eq!(
+            tracker.allowance(day + 180, 20, 25, 1),
+            (0, Some((LimitPeriod::Daily, 20)))
+        );
+
+        // the next day only the monthly limit is left
+        let next_day = day + SECS_PER_DAY;
+        assert_eq!(
+            tracker.allowance(next_day, 20, 25, 10),
+            (5, Some((LimitPeriod::Monthly, 25)))
+        );
+        tracker.record(next_day, 5);
+        assert_eq!(tracker.allowance(next_day, 20, 25, 1).0, 0);
+
+        // 2024-04-01
+        assert_eq!(
+            tracker.allowance(19_814 * SECS_PER_DAY, 20, 25, 10),
+            (10, None)
+        );
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::BTreeMap;
+
+use gdnative::api::TranslationServer;
+use gdnative::core_types::VariantDispatch;
+use gdnative::prelude::*;
+
+/// How numbers are written in a language.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Style {
+    grouping: &'static str,
+    decimal: char,
+    /// Abbreviation units from the largest down, e.g. `1.2M`.
+    units: &'static [(f64, &'static str)],
+    /// Currency symbol goes before the amount (`$1.99`) rather than after it (`1,99 €`).
+    symbol_first: bool,
+}
+
+const WESTERN_UNITS: &[(f64, &str)] = &[(1e9, "B"), (1e6, "M"), (1e3, "K")];
+
+const EN: Style = Style {
+    grouping: ",",
+    decimal: '.',
+    units: WESTERN_UNITS,
+    symbol_first: true,
+};
+
+/// Language of a locale code such as `"en"`, `"de_DE"` or `"pt-BR"`.
+fn language(locale: &str) -> String {
+    locale
+        .split(['_', '-'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+/// Style for a locale code, English when unknown.
+fn style(locale: &str) -> Style {
+    match language(locale).as_str() {
+        "de" | "es" | "it" | "pt" | "nl" | "id" | "tr" | "da" => Style {
+            grouping: ".",
+            decimal: ',',
+            symbol_first: false,
+            ..EN
+        },
+        // narrow no-break space, so the groups never wrap apart
+        "fr" | "ru" | "pl" | "uk" | "cs" | "sv" | "nb" | "fi" => Style {
+            grouping: "\u{202f}",
+            decimal: ',',
+            symbol_first: false,
+            ..EN
+        },
+        "ja" => Style {
+            units: &[(1e8, "億"), (1e4, "万")],
+            ..EN
+        },
+        "zh" => Style {
+            units: &[(1e8, "亿"), (1e4, "万")],
+            ..EN
+        },
+        "ko" => Style {
+            units: &[(1e8, "억"), (1e4, "만")],
+            ..EN
+        },
+        _ => EN,
+    }
+}
+
+/// Whole number with the locale's thousands separators.
+fn group(value: i64, style: &Style) -> String {
+    let digits = value.unsigned_abs().to_string();
+    let mut grouped = String::new();
+    for (idx, digit) in digits.chars().enumerate() {
+        if idx > 0 && (digits.len() - idx).is_multiple_of(3) {
+            grouped.push_str(style.grouping);
+        }
+        grouped.push(digit);
+    }
+    if value < 0 {
+        grouped.insert(0, '-');
+    }
+    grouped
+}
+
+/// `value` with `decimals` digits after the locale's decimal separator.
+fn decimal(value: f64, decimals: usize, style: &Style) -> String {
+    let scale = 10_i64.pow(decimals as u32);
+    let scaled = (value.abs() * scale as f64).round() as i64;
+    let sign = if value < 0.0 && scaled != 0 { "-" } else { "" };
+    let whole = group(scaled / scale, style);
+    if decimals == 0 {
+        return format!("{sign}{whole}");
+    }
+    let fraction = scaled % scale;
+    format!("{sign}{whole}{}{fraction:0decimals$}", style.decimal)
+}
+
+/// CLDR plural category of the whole number `n` in `language`, `"other"` when unknown.
+fn plural_category(language: &str, n: u64) -> &'static str {
+    let (ones, tens) = (n % 10, n % 100);
+    let few = (2..=4).contains(&ones) && !(12..=14).contains(&tens);
+    match language {
+        "ja" | "zh" | "ko" | "id" => "other",
+        "fr" | "pt" if n <= 1 => "one",
+        "ru" | "uk" if ones == 1 && tens != 11 => "one",
+        "ru" | "uk" | "pl" if few => "few",
+        "ru" | "uk" => "many",
+        "pl" if n == 1 => "one",
+        "pl" => "many",
+        "cs" if n == 1 => "one",
+        "cs" if (2..=4).contains(&n) => "few",
+        "fr" | "pt" | "cs" => "other",
+        _ if n == 1 => "one",
+        _ => "other",
+    }
+}
+
+/// Value filled into a message.
+#[derive(Debug, Clone, PartialEq)]
+enum Arg {
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl Arg {
+    fn from_variant(value: &Variant) -> Self {
+        match value.dispatch() {
+            VariantDispatch::I64(value) => Arg::Int(value),
+            VariantDispatch::F64(value) => Arg::Float(value),
+            _ => Arg::Text(value.to_string()),
+        }
+    }
+
+    /// Numbers with the locale's separators, floats with at most two decimals.
+    fn format(&self, style: &Style) -> String {
+        match self {
+            Arg::Int(value) => group(*value, style),
+            Arg::Float(value) => {
+                let formatted = decimal(*value, 2, style);
+                let trimmed = formatted.trim_end_matches('0');
+                trimmed.trim_end_matches(style.decimal).to_string()
+            }
+            Arg::Text(text) => text.clone(),
+        }
+    }
+
+    /// The whole number to select plural branches with, `None` for fractions and text.
+    fn whole(&self) -> Option<i64> {
+        match self {
+            Arg::Int(value) => Some(*value),
+            Arg::Float(value) if value.fract() == 0.0 && value.abs() < 1e15 => Some(*value as i64),
+            _ => None,
+        }
+    }
+}
+
+/// Byte index of the `}` closing the `{` that `text` starts with.
+fn closing_brace(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (idx, c) in text.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Fill `{name}` placeholders and `{name, plural, ...}` branches of `template` with `args`.
+///
+/// `number` is what `#` stands for inside a plural branch. Placeholders without an argument
+/// and unbalanced braces are kept as they are, so a broken translation still shows its text.
+/// Other placeholder kinds (e.g. `select`) are kept too, with a warning.
+fn fill(
+    template: &str,
+    args: &BTreeMap<String, Arg>,
+    locale: &str,
+    number: Option<&str>,
+) -> String {
+    let mut filled = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(|c| c == '{' || (c == '#' && number.is_some())) {
+        filled.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let (Some(number), Some(after)) = (number, rest.strip_prefix('#')) {
+            filled.push_str(number);
+            rest = after;
+            continue;
+        }
+        let Some(end) = closing_brace(rest) else {
+            break;
+        };
+        filled.push_str(&placeholder(&rest[1..end], args, locale));
+        rest = &rest[end + 1..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+fn placeholder(inner: &str, args: &BTreeMap<String, Arg>, locale: &str) -> String {
+    let mut parts = inner.splitn(3, ',');
+    let name = parts.next().unwrap_or_default().trim();
+    let kind = parts.next().map(str::trim);
+    let (Some(arg), branches) = (args.get(name), parts.next()) else {
+        return format!("{{{inner}}}");
+    };
+    let style = style(locale);
+    match (kind, branches) {
+        (None, _) => arg.format(&style),
+        (Some("plural"), Some(branches)) => {
+            let branches = plural_branches(branches);
+            let exact = arg
+                .whole()
+                .and_then(|n| branches.get(format!("={n}").as_str()).copied());
+            let category = arg.whole().map_or("other", |n| {
+                plural_category(&language(locale), n.unsigned_abs())
+            });
+            match exact.or_else(|| branches.get(category).or(branches.get("other")).copied()) {
+                Some(branch) => fill(branch, args, locale, Some(&arg.format(&style))),
+                None => format!("{{{inner}}}"),
+            }
+        }
+        _ => {
+            godot_warn!("unsupported placeholder in message, kept as written: {{{inner}}}");
+            format!("{{{inner}}}")
+        }
+    }
+}
+
+/// `one {# gem} other {# gems}` as `{ "one": "# gem", "other": "# gems" }`.
+fn plural_branches(mut text: &str) -> BTreeMap<&str, &str> {
+    let mut branches = BTreeMap::new();
+    loop {
+        text = text.trim_start();
+        let Some(open) = text.find('{') else {
+            return branches;
+        };
+        let Some(end) = closing_brace(&text[open..]) else {
+            return branches;
+        };
+        branches.insert(text[..open].trim(), &text[open + 1..open + end]);
+        text = &text[open + end + 1..];
+    }
+}
+
+/// Formats currency amounts and prices consistently for UI labels.
+///
+/// Set `locale` to the player's locale (e.g. from `Settings.locale`), unknown locales are
+/// formatted like English.
+#[derive(NativeClass)]
+#[inherit(Reference)]
+pub struct NumberFormat {
+    #[property]
+    locale: String,
+}
+
+#[methods]
+impl NumberFormat {
+    fn new(_owner: &Reference) -> Self {
+        NumberFormat {
+            locale: "en".into(),
+        }
+    }
+
+    /// `value` with thousands separators, e.g. `1,234,567`.
+    #[method]
+    fn format_number(&self, value: i64) -> String {
+        group(value, &style(&self.locale))
+    }
+
+    /// `value` abbreviated to one decimal when it's large, e.g. `1.2M` or `12万`.
+    ///
+    /// Values below the smallest unit are formatted like `format_number`.
+    #[method]
+    fn format_compact(&self, value: i64) -> String {
+        let style = style(&self.locale);
+        let abs = value.unsigned_abs() as f64;
+        let Some(&(unit, suffix)) = style.units.iter().find(|(unit, _)| abs >= *unit) else {
+            return group(value, &style);
+        };
+        // truncate rather than round, so 999_999 shows as 999K and not 1,000K
+        let scaled = value as f64 / unit;
+        let scaled = if scaled.abs() >= 100.0 {
+            scaled.trunc()
+        } else {
+            (scaled * 10.0).trunc() / 10.0
+        };
+        let decimals = if scaled.fract() == 0.0 { 0 } else { 1 };
+        format!("{}{suffix}", decimal(scaled, decimals, &style))
+    }
+
+    /// Price with two decimals and `symbol` placed the locale's way, e.g. `$4.99` or `4,99 €`.
+    #[method]
+    fn format_price(&self, amount: f64, symbol: String) -> String {
+        let style = style(&self.locale);
+        let amount = decimal(amount, 2, &style);
+        if style.symbol_first {
+            format!("{symbol}{amount}")
+        } else {
+            format!("{amount}\u{a0}{symbol}")
+        }
+    }
+
+    /// Translation of `key` with `args` filled in, e.g. `"You received 1,500 gems"`.
+    ///
+    /// `{name}` is replaced by `args[name]`, numbers formatted like `format_number`.
+    /// `{name, plural, =0 {no gems} one {# gem} other {# gems}}` picks the branch for the
+    /// number by the plural rules of `locale`, `#` standing for the formatted number.
+    /// Translations come from the `TranslationServer`, `locale` should match its locale.
+    #[method]
+    fn format_message(&self, key: String, args: Dictionary) -> String {
+        let template = TranslationServer::godot_singleton()
+            .translate(key)
+            .to_string();
+        let args = args
+            .iter()
+            .map(|(name, value)| (name.to_string(), Arg::from_variant(&value)))
+            .collect();
+        fill(&template, &args, &self.locale, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fill, plural_category, Arg, NumberFormat};
+    use std::collections::BTreeMap;
+
+    fn format(locale: &str) -> NumberFormat {
+        NumberFormat {
+            locale: locale.into(),
+        }
+    }
+
+    #[test]
+    fn grouping() {
+        assert_eq!(format("en").format_number(1_234_567), "1,234,567");
+        assert_eq!(format("en").format_number(-1_000), "-1,000");
+        assert_eq!(format("en").format_number(999), "999");
+        assert_eq!(format("de_DE").format_number(1_234_567), "1.234.567");
+        assert_eq!(format("fr").format_number(12_345), "12\u{202f}345");
+        assert_eq!(
+            format("xx").format_number(i64::MIN),
+            "-9,223,372,036,854,775,808"
+        );
+    }
+
+    #[test]
+    fn compact() {
+        let en = format("en");
+        assert_eq!(en.format_compact(950), "950");
+        assert_eq!(en.format_compact(1_200), "1.2K");
+        assert_eq!(en.format_compact(1_000_000), "1M");
+        assert_eq!(en.format_compact(999_999), "999K");
+        assert_eq!(en.format_compact(-2_560_000), "-2.5M");
+        assert_eq!(format("de").format_compact(1_250_000), "1,2M");
+        assert_eq!(format("ja").format_compact(123_000), "12.3万");
+        assert_eq!(format("ko_KR").format_compact(300_000_000), "3억");
+    }
+
+    #[test]
+    fn prices() {
+        assert_eq!(format("en").format_price(4.99, "$".into()), "$4.99");
+        assert_eq!(
+            format("de").format_price(1234.5, "€".into()),
+            "1.234,50\u{a0}€"
+        );
+        assert_eq!(format("ja").format_price(-0.001, "¥".into()), "¥0.00");
+    }
+
+    #[test]
+    fn plural_categories() {
+        let categories = |language, numbers: &[u64]| -> Vec<&str> {
+            numbers
+                .iter()
+                .map(|n| plural_category(language, *n))
+                .collect()
+        };
+        assert_eq!(categories("en", &[0, 1, 2]), ["other", "one", "other"]);
+        assert_eq!(categories("fr", &[0, 1, 2]), ["one", "one", "other"]);
+        assert_eq!(
+            categories("ru", &[1, 3, 5, 11, 21, 22, 112]),
+            ["one", "few", "many", "many", "one", "few", "many"]
+        );
+        assert_eq!(
+            categories("pl", &[1, 2, 5, 21]),
+            ["one", "few", "many", "many"]
+        );
+        assert_eq!(categories("ja", &[1]), ["other"]);
+    }
+
+    #[test]
+    fn messages() {
+        let args = BTreeMap::from([
+            ("gems".to_string(), Arg::Int(1_500)),
+            ("one".to_string(), Arg::Int(1)),
+            ("none".to_string(), Arg::Int(0)),
+            ("hours".to_string(), Arg::Float(1.5)),
+            ("player".to_string(), Arg::Text("Alice".into())),
+        ]);
+        let gems = "{player} received {gems, plural, =0 {no gems} one {# gem} other {# gems}}";
+        assert_eq!(fill(gems, &args, "en", None), "Alice received 1,500 gems");
+        let one = gems.replace("{gems", "{one");
+        assert_eq!(fill(&one, &args, "en", None), "Alice received 1 gem");
+        let none = gems.replace("{gems", "{none");
+        assert_eq!(fill(&none, &args, "en", None), "Alice received no gems");
+        assert_eq!(
+            fill(
+                "{gems, plural, one {# Juwel} other {# Juwelen}}",
+                &args,
+                "de",
+                None
+            ),
+            "1.500 Juwelen"
+        );
+        assert_eq!(fill("{hours} h", &args, "de", None), "1,5 h");
+        // missing arguments and broken templates keep their text, `#` is only special in branches
+        assert_eq!(fill("#1 {missing}", &args, "en", None), "#1 {missing}");
+        assert_eq!(fill("{gems", &args, "en", None), "{gems");
+        let select = "{player, select, Alice {her} other {their}} gems";
+        assert_eq!(fill(select, &args, "en", None), select);
+    }
+}
@@ -0,0 +1,61 @@
+use gdnative::{api::OS, prelude::*};
+use std::{
+    backtrace::Backtrace,
+    fs::OpenOptions,
+    io::Write,
+    panic,
+    path::{Path, PathBuf},
+    sync::Once,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Crash log file name, created inside Godot's `user://` directory.
+const CRASH_LOG_FILE: &str = "native_crash.log";
+
+/// Forward panics to `godot_error!` and append them to a crash log,
+/// so native crashes during pulls show up in the editor output and in player reports.
+///
+/// The previously installed hook still runs afterwards. Only the first call installs the hook,
+/// `init` runs again on editor hot-reloads and must not stack one hook onto another.
+pub(crate) fn install() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(install_hook);
+}
+
+fn install_hook() {
+    let user_dir = OS::godot_singleton().get_user_data_dir().to_string();
+    let log_path = PathBuf::from(user_dir).join(CRASH_LOG_FILE);
+    let default_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        let payload = info.payload();
+        let msg = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("<non-string panic payload>");
+        let location = info
+            .location()
+            .map(|loc| loc.to_string())
+            .unwrap_or_else(|| "<unknown location>".to_string());
+        let report = format!(
+            "native panic at {location}: {msg}\n{}",
+            Backtrace::force_capture()
+        );
+
+        godot_error!("{report}");
+        if let Err(e) = append_crash_log(&log_path, &report) {
+            godot_error!("unable to write crash log '{}': {e}", log_path.display());
+        }
+        default_hook(info);
+    }));
+}
+
+fn append_crash_log(path: &Path, report: &str) -> std::io::Result<()> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "[{timestamp}] {report}")
+}
@@ -0,0 +1,290 @@
+use gdnative::prelude::*;
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+/// A* pathfinding over a weighted grid of cells.
+///
+/// Build the grid from tile ids with `build`, then adjust single cells with `set_weight`
+/// and `set_obstacle`. Cells are addressed with `Vector2` grid coordinates like `TileMap` cells.
+#[derive(NativeClass, Default)]
+#[inherit(Reference)]
+pub struct GridPathfinder {
+    /// Also move diagonally, never cutting corners past obstacles.
+    #[property]
+    diagonal: bool,
+    width: i32,
+    height: i32,
+    /// Cost of entering each cell, row by row, `None` for obstacles.
+    costs: Vec<Option<f32>>,
+}
+
+#[methods]
+impl GridPathfinder {
+    fn new(_owner: &Reference) -> Self {
+        Self::default()
+    }
+
+    /// Replace the grid with `width` x `height` cells built from `tiles`, row by row.
+    ///
+    /// `tiles` holds a tile id per cell (e.g. from `TileMap.get_cell`), cells whose tile
+    /// is in `blocked_tiles` are obstacles and every other cell costs 1.
+    #[method]
+    fn build(&mut self, width: i32, height: i32, tiles: Vec<i32>, blocked_tiles: Vec<i32>) -> bool {
+        let expected = width.max(0) as usize * height.max(0) as usize;
+        if tiles.len() != expected {
+            godot_error!(
+                "GridPathfinder: {width}x{height} grid needs {expected} tiles, got {}",
+                tiles.len()
+            );
+            return false;
+        }
+        self.width = width;
+        self.height = height;
+        self.costs = tiles
+            .iter()
+            .map(|tile| (!blocked_tiles.contains(tile)).then_some(1.0))
+            .collect();
+        true
+    }
+
+    /// Set the cost of entering `cell`, which has to be positive. Returns `false` if it
+    /// isn't or `cell` is outside the grid.
+    #[method]
+    fn set_weight(&mut self, cell: Vector2, weight: f32) -> bool {
+        if !(weight.is_finite() && weight > 0.0) {
+            godot_error!("GridPathfinder: weight {weight} of {cell:?} is not a positive number");
+            return false;
+        }
+        self.set_cost(cell, Some(weight))
+    }
+
+    /// Block or unblock `cell`, unblocked cells cost 1. Returns `false` if `cell` is outside the grid.
+    #[method]
+    fn set_obstacle(&mut self, cell: Vector2, blocked: bool) -> bool {
+        self.set_cost(cell, (!blocked).then_some(1.0))
+    }
+
+    /// Cheapest path from `from` to `to`, both included, empty if there is none.
+    #[method]
+    fn find_path(&self, from: Vector2, to: Vector2) -> Vector2Array {
+        let (Some(start), Some(goal)) = (self.index(from), self.index(to)) else {
+            return Vector2Array::new();
+        };
+        self.search(start, goal)
+            .into_iter()
+            .map(|idx| self.cell(idx))
+            .collect()
+    }
+
+    fn set_cost(&mut self, cell: Vector2, cost: Option<f32>) -> bool {
+        match self.index(cell) {
+            Some(idx) => {
+                self.costs[idx] = cost;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn index(&self, cell: Vector2) -> Option<usize> {
+        let (x, y) = (cell.x.round() as i32, cell.y.round() as i32);
+        ((0..self.width).contains(&x) && (0..self.height).contains(&y))
+            .then(|| (y * self.width + x) as usize)
+    }
+
+    fn cell(&self, idx: usize) -> Vector2 {
+        let idx = idx as i32;
+        Vector2::new((idx % self.width) as f32, (idx / self.width) as f32)
+    }
+
+    fn walkable(&self, x: i32, y: i32) -> Option<(usize, f32)> {
+        if !(0..self.width).contains(&x) || !(0..self.height).contains(&y) {
+            return None;
+        }
+        let idx = (y * self.width + x) as usize;
+        self.costs[idx].map(|cost| (idx, cost))
+    }
+
+    /// Walkable neighbours of `idx` with the cost of moving there.
+    fn neighbours(&self, idx: usize) -> Vec<(usize, f32)> {
+        let (x, y) = (idx as i32 % self.width, idx as i32 / self.width);
+        let mut result: Vec<_> = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+            .into_iter()
+            .filter_map(|(dx, dy)| self.walkable(x + dx, y + dy))
+            .collect();
+        if self.diagonal {
+            for (dx, dy) in [(1, 1), (1, -1), (-1, 1), (-1, -1)] {
+                // both orthogonal neighbours have to be free, so paths never clip a corner
+                let free = self.walkable(x + dx, y).is_some() && self.walkable(x, y + dy).is_some();
+                if let Some((next, cost)) = self.walkable(x + dx, y + dy).filter(|_| free) {
+                    result.push((next, cost * std::f32::consts::SQRT_2));
+                }
+            }
+        }
+        result
+    }
+
+    /// Lower bound of the cost between two cells, keeps A* optimal.
+    fn heuristic(&self, from: usize, to: usize, min_cost: f32) -> f32 {
+        let (a, b) = (self.cell(from), self.cell(to));
+        let (dx, dy) = ((a.x - b.x).abs(), (a.y - b.y).abs());
+        let steps = if self.diagonal {
+            // octile distance
+            dx.max(dy) + (std::f32::consts::SQRT_2 - 1.0) * dx.min(dy)
+        } else {
+            dx + dy
+        };
+        steps * min_cost
+    }
+
+    fn search(&self, start: usize, goal: usize) -> Vec<usize> {
+        if self.costs[start].is_none() || self.costs[goal].is_none() {
+            return vec![];
+        }
+        let min_cost = self
+            .costs
+            .iter()
+            .flatten()
+            .copied()
+            .fold(f32::INFINITY, f32::min);
+
+        let mut open = BinaryHeap::new();
+        let mut best: HashMap<usize, f32> = HashMap::from([(start, 0.0)]);
+        let mut came_from: HashMap<usize, usize> = HashMap::new();
+        open.push(Open {
+            estimate: self.heuristic(start, goal, min_cost),
+            cost: 0.0,
+            idx: start,
+        });
+
+        while let Some(Open { cost, idx, .. }) = open.pop() {
+            if idx == goal {
+                let mut path = vec![goal];
+                while let Some(&prev) = came_from.get(path.last().unwrap()) {
+                    path.push(prev);
+                }
+                path.reverse();
+                return path;
+            }
+            if cost > best[&idx] {
+                // stale entry, a cheaper way here was found after it was queued
+                continue;
+            }
+            for (next, step) in self.neighbours(idx) {
+                let next_cost = cost + step;
+                if best.get(&next).is_none_or(|&known| next_cost < known) {
+                    best.insert(next, next_cost);
+                    came_from.insert(next, idx);
+                    open.push(Open {
+                        estimate: next_cost + self.heuristic(next, goal, min_cost),
+                        cost: next_cost,
+                        idx: next,
+                    });
+                }
+            }
+        }
+        vec![]
+    }
+}
+
+/// Open set entry, ordered so the `BinaryHeap` pops the lowest estimate first.
+struct Open {
+    estimate: f32,
+    cost: f32,
+    idx: usize,
+}
+
+impl PartialEq for Open {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GridPathfinder;
+    use gdnative::prelude::*;
+
+    const WALL: i32 = 1;
+
+    /// Grid from rows of `.` (floor) and `#` (wall).
+    fn grid(rows: &[&str], diagonal: bool) -> GridPathfinder {
+        let tiles: Vec<i32> = rows
+            .iter()
+            .flat_map(|row| row.chars().map(|c| if c == '#' { WALL } else { 0 }))
+            .collect();
+        let mut finder = GridPathfinder {
+            diagonal,
+            ..Default::default()
+        };
+        assert!(finder.build(rows[0].len() as i32, rows.len() as i32, tiles, vec![WALL]));
+        finder
+    }
+
+    fn path(finder: &GridPathfinder, from: (f32, f32), to: (f32, f32)) -> Vec<(f32, f32)> {
+        finder
+            .find_path(Vector2::new(from.0, from.1), Vector2::new(to.0, to.1))
+            .to_vec()
+            .into_iter()
+            .map(|cell| (cell.x, cell.y))
+            .collect()
+    }
+
+    #[test]
+    fn around_walls() {
+        let finder = grid(&["..#..", "..#..", "....."], false);
+        let found = path(&finder, (0.0, 0.0), (4.0, 0.0));
+        assert_eq!(found.len(), 9);
+        assert_eq!(found.first(), Some(&(0.0, 0.0)));
+        assert_eq!(found.last(), Some(&(4.0, 0.0)));
+        assert!(found.contains(&(2.0, 2.0)));
+
+        assert!(path(&finder, (0.0, 0.0), (2.0, 0.0)).is_empty());
+        assert!(path(&finder, (0.0, 0.0), (9.0, 0.0)).is_empty());
+        assert_eq!(path(&finder, (1.0, 1.0), (1.0, 1.0)), vec![(1.0, 1.0)]);
+    }
+
+    #[test]
+    fn weights_and_obstacles() {
+        let mut finder = grid(&["...", "...", "..."], false);
+        assert_eq!(path(&finder, (0.0, 1.0), (2.0, 1.0)).len(), 3);
+
+        assert!(finder.set_weight(Vector2::new(1.0, 1.0), 5.0));
+        let detour = path(&finder, (0.0, 1.0), (2.0, 1.0));
+        assert_eq!(detour.len(), 5);
+        assert!(!detour.contains(&(1.0, 1.0)));
+
+        for y in 0..3 {
+            assert!(finder.set_obstacle(Vector2::new(1.0, y as f32), true));
+        }
+        assert!(path(&finder, (0.0, 1.0), (2.0, 1.0)).is_empty());
+        assert!(!finder.set_obstacle(Vector2::new(3.0, 0.0), true));
+        assert!(!finder.set_weight(Vector2::new(0.0, 0.0), 0.0));
+    }
+
+    #[test]
+    fn diagonal_moves() {
+        let open = grid(&["...", "...", "..."], true);
+        assert_eq!(path(&open, (0.0, 0.0), (2.0, 2.0)).len(), 3);
+
+        // no squeezing between two diagonal walls
+        let corner = grid(&[".#", "#."], true);
+        assert!(path(&corner, (0.0, 0.0), (1.0, 1.0)).is_empty());
+    }
+}
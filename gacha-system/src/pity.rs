@@ -0,0 +1,517 @@
+use gdnative::prelude::*;
+use std::{fmt, str::FromStr};
+
+use crate::{
+    error::{GachaError, Result},
+    gacha_core::Rarity,
+};
+
+/// Pity counters and thresholds a strategy decides on, `0` disables a threshold.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ToVariant)]
+pub struct PityCounters {
+    /// Pulls since the last SR or better.
+    pub pity_count: u32,
+    /// Pulls since the last SSR.
+    pub hard_pity_count: u32,
+    pub pity: u32,
+    pub hard_pity: u32,
+}
+
+/// A pity design: how the rates of the next pull change with the pity counters.
+///
+/// Strategies only adjust rates, sampling and counter bookkeeping stay in `GachaSystem`.
+pub trait PityStrategy {
+    /// Rates to roll the next pull with, `None` to use the base `rarities` unchanged.
+    fn adjust_rates(
+        &self,
+        counters: PityCounters,
+        rarities: &[(Rarity, f64)],
+    ) -> Option<Vec<(Rarity, f64)>>;
+
+    /// Progress towards the guarantees this strategy makes, none by default.
+    fn progress(&self, _counters: PityCounters) -> PityProgress {
+        PityProgress::default()
+    }
+}
+
+/// Progress towards the SR-or-better (`soft`) and SSR (`hard`) guarantees as fractions in
+/// `0..=1`, where `1` means the next pull is guaranteed. Always `0` for a guarantee the
+/// strategy doesn't make.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PityProgress {
+    pub soft: f64,
+    pub hard: f64,
+}
+
+/// Run `rarities` through each of `modifiers` in order, `None` if none of them changed the rates.
+pub fn apply_modifiers(
+    modifiers: &[&dyn PityStrategy],
+    counters: PityCounters,
+    rarities: &[(Rarity, f64)],
+) -> Option<Vec<(Rarity, f64)>> {
+    modifiers.iter().fold(None, |adjusted, modifier| {
+        let rates = adjusted.as_deref().unwrap_or(rarities);
+        modifier.adjust_rates(counters, rates).or(adjusted)
+    })
+}
+
+/// Whether the next pull reaches `threshold`, `0` never does.
+///
+/// Counts past the threshold still reach it, e.g. after a guaranteed SSR fell back to an SR
+/// because every SSR is disabled, so the guarantee carries over instead of being skipped.
+pub(crate) fn reaches(count: u32, threshold: u32) -> bool {
+    threshold != 0 && count.saturating_add(1) >= threshold
+}
+
+/// Fraction of the pulls made towards a pity `threshold`, `1.0` exactly when the next pull
+/// [`reaches`] it.
+///
+/// A threshold of `0` disables that pity, so there's never any progress.
+pub(crate) fn pity_fraction(count: u32, threshold: u32) -> f64 {
+    if threshold == 0 {
+        0.0
+    } else if reaches(count, threshold) {
+        1.0
+    } else {
+        f64::from(count) / f64::from(threshold - 1)
+    }
+}
+
+fn only(rarities: &[(Rarity, f64)], keep: impl Fn(Rarity) -> bool) -> Vec<(Rarity, f64)> {
+    rarities.iter().filter(|(r, _)| keep(*r)).cloned().collect()
+}
+
+/// No pity at all, every pull uses the base rates.
+#[derive(Debug, Clone, Copy)]
+pub struct NoPity;
+
+impl PityStrategy for NoPity {
+    fn adjust_rates(&self, _: PityCounters, _: &[(Rarity, f64)]) -> Option<Vec<(Rarity, f64)>> {
+        None
+    }
+}
+
+/// Only the hard pity: an SSR is guaranteed once `hard_pity` is reached, `pity` is ignored.
+#[derive(Debug, Clone, Copy)]
+pub struct HardCutoff;
+
+impl PityStrategy for HardCutoff {
+    fn adjust_rates(
+        &self,
+        counters: PityCounters,
+        rarities: &[(Rarity, f64)],
+    ) -> Option<Vec<(Rarity, f64)>> {
+        reaches(counters.hard_pity_count, counters.hard_pity)
+            .then(|| only(rarities, |r| r == Rarity::SSR))
+    }
+
+    fn progress(&self, counters: PityCounters) -> PityProgress {
+        PityProgress {
+            soft: 0.0,
+            hard: pity_fraction(counters.hard_pity_count, counters.hard_pity),
+        }
+    }
+}
+
+/// A guarantee per rarity tier: SR or better at `pity`, SSR at `hard_pity`.
+#[derive(Debug, Clone, Copy)]
+pub struct PerRarity;
+
+impl PityStrategy for PerRarity {
+    fn adjust_rates(
+        &self,
+        counters: PityCounters,
+        rarities: &[(Rarity, f64)],
+    ) -> Option<Vec<(Rarity, f64)>> {
+        HardCutoff.adjust_rates(counters, rarities).or_else(|| {
+            reaches(counters.pity_count, counters.pity).then(|| only(rarities, |r| r <= Rarity::SR))
+        })
+    }
+
+    fn progress(&self, counters: PityCounters) -> PityProgress {
+        PityProgress {
+            soft: pity_fraction(counters.pity_count, counters.pity),
+            ..HardCutoff.progress(counters)
+        }
+    }
+}
+
+/// Like [`PerRarity`], but the SSR chance also climbs linearly once `pity` pulls went by
+/// without one, until it's certain at `hard_pity`.
+#[derive(Debug, Clone, Copy)]
+pub struct SoftCurve;
+
+impl PityStrategy for SoftCurve {
+    fn adjust_rates(
+        &self,
+        counters: PityCounters,
+        rarities: &[(Rarity, f64)],
+    ) -> Option<Vec<(Rarity, f64)>> {
+        let guaranteed = PerRarity.adjust_rates(counters, rarities);
+        let PityCounters {
+            hard_pity_count,
+            pity,
+            hard_pity,
+            ..
+        } = counters;
+        // the ramp needs a start before the hard pity, otherwise there's nothing to curve
+        if pity == 0 || hard_pity <= pity || hard_pity_count + 1 < pity {
+            return guaranteed;
+        }
+        let base = guaranteed.unwrap_or_else(|| rarities.to_vec());
+        let progress = f64::from(hard_pity_count + 1 - pity) / f64::from(hard_pity - pity);
+        Some(with_ssr_chance(base, |chance| {
+            chance + (1.0 - chance) * progress
+        }))
+    }
+
+    fn progress(&self, counters: PityCounters) -> PityProgress {
+        PerRarity.progress(counters)
+    }
+}
+
+/// Soft pity that adds `step` to the SSR chance for every pull from the `start`th one
+/// without an SSR, e.g. `+6%` per pull from pull 74. A `start` of `0` disables it.
+///
+/// Only a rate modifier, run before the pity strategy by `GachaSystem` so the strategy's
+/// guarantees still apply.
+#[derive(Debug, Clone, Copy)]
+pub struct SoftRamp {
+    pub start: u32,
+    pub step: f64,
+}
+
+impl SoftRamp {
+    fn enabled(&self) -> bool {
+        self.start != 0 && self.step.is_finite() && self.step > 0.0
+    }
+
+    /// Fraction of the pulls made towards the first ramped one, `1.0` once the SSR chance of
+    /// the next pull is raised and `0` while the ramp is disabled.
+    pub fn fraction(&self, hard_pity_count: u32) -> f64 {
+        if self.enabled() {
+            pity_fraction(hard_pity_count, self.start)
+        } else {
+            0.0
+        }
+    }
+}
+
+impl PityStrategy for SoftRamp {
+    fn adjust_rates(
+        &self,
+        counters: PityCounters,
+        rarities: &[(Rarity, f64)],
+    ) -> Option<Vec<(Rarity, f64)>> {
+        let pull = counters.hard_pity_count + 1;
+        if !self.enabled() || pull < self.start {
+            return None;
+        }
+        let ramped = f64::from(pull - self.start + 1) * self.step;
+        Some(with_ssr_chance(rarities.to_vec(), |chance| chance + ramped))
+    }
+}
+
+/// Scale the SSR rate so its share of the total becomes `chance` of its current share,
+/// keeping the other rates as they are. Only SSR is left once the chance reaches `1`.
+fn with_ssr_chance(
+    rates: Vec<(Rarity, f64)>,
+    chance: impl FnOnce(f64) -> f64,
+) -> Vec<(Rarity, f64)> {
+    let ssr: f64 = rates
+        .iter()
+        .filter(|(r, _)| *r == Rarity::SSR)
+        .map(|(_, rate)| rate)
+        .sum();
+    let others: f64 = rates
+        .iter()
+        .filter(|(r, _)| *r != Rarity::SSR)
+        .map(|(_, rate)| rate)
+        .sum();
+    if ssr <= 0.0 || others <= 0.0 {
+        return rates;
+    }
+    let chance = chance(ssr / (ssr + others)).min(1.0);
+    if chance >= 1.0 {
+        return only(&rates, |r| r == Rarity::SSR);
+    }
+    let scale = chance * others / (1.0 - chance) / ssr;
+    rates
+        .into_iter()
+        .map(|(r, rate)| (r, if r == Rarity::SSR { rate * scale } else { rate }))
+        .collect()
+}
+
+/// Pity design selected by the `pity_strategy` property.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum PityKind {
+    None,
+    HardCutoff,
+    #[default]
+    PerRarity,
+    SoftCurve,
+}
+
+impl PityKind {
+    pub const ALL: [PityKind; 4] = [
+        PityKind::None,
+        PityKind::HardCutoff,
+        PityKind::PerRarity,
+        PityKind::SoftCurve,
+    ];
+
+    pub fn strategy(self) -> &'static dyn PityStrategy {
+        match self {
+            PityKind::None => &NoPity,
+            PityKind::HardCutoff => &HardCutoff,
+            PityKind::PerRarity => &PerRarity,
+            PityKind::SoftCurve => &SoftCurve,
+        }
+    }
+}
+
+impl fmt::Display for PityKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PityKind::None => "none",
+            PityKind::HardCutoff => "hard_cutoff",
+            PityKind::PerRarity => "per_rarity",
+            PityKind::SoftCurve => "soft_curve",
+        })
+    }
+}
+
+impl FromStr for PityKind {
+    type Err = GachaError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        PityKind::ALL
+            .into_iter()
+            .find(|kind| kind.to_string() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = PityKind::ALL.iter().map(ToString::to_string).collect();
+                GachaError::InvalidProperty(
+                    "pity_strategy".to_string(),
+                    format!(
+                        "unknown strategy \"{s}\", expected one of {}",
+                        names.join(", ")
+                    ),
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        apply_modifiers, pity_fraction, reaches, PerRarity, PityCounters, PityKind, PityProgress,
+        PityStrategy, SoftCurve, SoftRamp,
+    };
+    use crate::gacha_core::Rarity;
+
+    const RARITIES: &[(Rarity, f64)] = &[
+        (Rarity::SSR, 0.05),
+        (Rarity::SR, 0.2),
+        (Rarity::R, 0.4),
+        (Rarity::N, 0.35),
+    ];
+
+    fn counters(pity_count: u32, hard_pity_count: u32) -> PityCounters {
+        PityCounters {
+            pity_count,
+            hard_pity_count,
+            pity: 10,
+            hard_pity: 50,
+        }
+    }
+
+    fn rarities_of(rates: Option<Vec<(Rarity, f64)>>) -> Option<Vec<Rarity>> {
+        rates.map(|rates| rates.into_iter().map(|(r, _)| r).collect())
+    }
+
+    #[test]
+    fn guarantees() {
+        let adjust = |kind: PityKind, c| rarities_of(kind.strategy().adjust_rates(c, RARITIES));
+
+        for kind in [PityKind::None, PityKind::HardCutoff, PityKind::PerRarity] {
+            assert_eq!(adjust(kind, counters(3, 3)), None, "{kind}");
+        }
+        assert_eq!(adjust(PityKind::None, counters(9, 49)), None);
+
+        assert_eq!(adjust(PityKind::HardCutoff, counters(9, 9)), None);
+        assert_eq!(
+            adjust(PityKind::HardCutoff, counters(0, 49)),
+            Some(vec![Rarity::SSR])
+        );
+
+        assert_eq!(
+            adjust(PityKind::PerRarity, counters(9, 9)),
+            Some(vec![Rarity::SSR, Rarity::SR])
+        );
+        assert_eq!(
+            adjust(PityKind::PerRarity, counters(9, 49)),
+            Some(vec![Rarity::SSR])
+        );
+        // a threshold of 0 disables that pity
+        let disabled = PityCounters::default();
+        assert_eq!(adjust(PityKind::PerRarity, disabled), None);
+        // a guarantee that was missed still holds on the next pull
+        assert_eq!(
+            adjust(PityKind::HardCutoff, counters(0, 52)),
+            Some(vec![Rarity::SSR])
+        );
+    }
+
+    #[test]
+    fn soft_curve_ramps_to_ssr() {
+        let ssr_chance = |hard_pity_count| {
+            let rates = SoftCurve
+                .adjust_rates(counters(0, hard_pity_count), RARITIES)
+                .unwrap_or_else(|| RARITIES.to_vec());
+            let total: f64 = rates.iter().map(|(_, rate)| rate).sum();
+            let ssr: f64 = rates
+                .iter()
+                .filter(|(r, _)| *r == Rarity::SSR)
+                .map(|(_, rate)| rate)
+                .sum();
+            ssr / total
+        };
+
+        assert!((ssr_chance(0) - 0.05).abs() < 1e-9);
+        assert!((ssr_chance(9) - 0.05).abs() < 1e-9);
+        let mut last = ssr_chance(9);
+        for count in 10..49 {
+            let chance = ssr_chance(count);
+            assert!(chance > last, "{count}: {chance} <= {last}");
+            last = chance;
+        }
+        assert_eq!(ssr_chance(49), 1.0);
+    }
+
+    fn ssr_share(rates: &[(Rarity, f64)]) -> f64 {
+        let total: f64 = rates.iter().map(|(_, rate)| rate).sum();
+        let ssr: f64 = rates
+            .iter()
+            .filter(|(r, _)| *r == Rarity::SSR)
+            .map(|(_, rate)| rate)
+            .sum();
+        ssr / total
+    }
+
+    #[test]
+    fn soft_ramp() {
+        let ramp = SoftRamp {
+            start: 74,
+            step: 0.06,
+        };
+        let counters = |hard_pity_count| PityCounters {
+            hard_pity_count,
+            ..Default::default()
+        };
+        assert_eq!(ramp.adjust_rates(counters(72), RARITIES), None);
+        // the 74th pull without an SSR is the first one ramped up
+        let at = |count| ssr_share(&ramp.adjust_rates(counters(count), RARITIES).unwrap());
+        assert!((at(73) - 0.11).abs() < 1e-9);
+        assert!((at(79) - 0.47).abs() < 1e-9);
+        assert_eq!(at(89), 1.0);
+        let off = SoftRamp { start: 0, ..ramp };
+        assert_eq!(off.adjust_rates(counters(100), RARITIES), None);
+
+        // the progress bar fills exactly when the ramp kicks in
+        assert_eq!(ramp.fraction(0), 0.0);
+        assert!(ramp.fraction(72) < 1.0);
+        assert_eq!(ramp.fraction(73), 1.0);
+        assert_eq!(off.fraction(100), 0.0);
+        assert_eq!(SoftRamp { step: 0.0, ..ramp }.fraction(100), 0.0);
+    }
+
+    #[test]
+    fn modifier_pipeline() {
+        let ramp = SoftRamp {
+            start: 5,
+            step: 0.1,
+        };
+        let modifiers: [&dyn PityStrategy; 2] = [&ramp, &PerRarity];
+        assert_eq!(apply_modifiers(&modifiers, counters(0, 0), RARITIES), None);
+
+        // the ramp carries through the SR-or-better guarantee
+        let rates = apply_modifiers(&modifiers, counters(9, 9), RARITIES).unwrap();
+        assert_eq!(
+            rarities_of(Some(rates.clone())),
+            Some(vec![Rarity::SSR, Rarity::SR])
+        );
+        let ramped = ssr_share(&ramp.adjust_rates(counters(9, 9), RARITIES).unwrap());
+        assert!(ssr_share(&rates) > ramped);
+        assert_eq!(
+            rarities_of(apply_modifiers(&modifiers, counters(0, 49), RARITIES)),
+            Some(vec![Rarity::SSR])
+        );
+    }
+
+    #[test]
+    fn fractions() {
+        assert_eq!(pity_fraction(0, 10), 0.0);
+        assert_eq!(pity_fraction(3, 5), 0.75);
+        // the 10th pull is the guaranteed one
+        assert_eq!(pity_fraction(9, 10), 1.0);
+        assert_eq!(pity_fraction(12, 10), 1.0);
+        assert_eq!(pity_fraction(0, 1), 1.0);
+        assert_eq!(pity_fraction(5, 0), 0.0);
+        // full progress is shown exactly when the pity strategy guarantees the pull
+        for count in 0..12 {
+            let guaranteed = reaches(count, 10);
+            assert_eq!(pity_fraction(count, 10) == 1.0, guaranteed, "{count}");
+        }
+    }
+
+    #[test]
+    fn progress_per_strategy() {
+        let progress = |kind: PityKind, c| kind.strategy().progress(c);
+        let full = counters(9, 49);
+
+        assert_eq!(progress(PityKind::None, full), PityProgress::default());
+        // the soft threshold guarantees nothing under a hard cutoff
+        assert_eq!(
+            progress(PityKind::HardCutoff, full),
+            PityProgress {
+                soft: 0.0,
+                hard: 1.0
+            }
+        );
+        for kind in [PityKind::PerRarity, PityKind::SoftCurve] {
+            assert_eq!(
+                progress(kind, full),
+                PityProgress {
+                    soft: 1.0,
+                    hard: 1.0
+                },
+                "{kind}"
+            );
+            assert_eq!(progress(kind, counters(3, 7)).soft, 3.0 / 9.0, "{kind}");
+        }
+        // full progress is shown exactly when the strategy guarantees the pull
+        for kind in PityKind::ALL {
+            for count in 0..52 {
+                let c = counters(count, count);
+                let guaranteed = rarities_of(kind.strategy().adjust_rates(c, RARITIES));
+                let shown = progress(kind, c);
+                let expected = if shown.hard == 1.0 {
+                    Some(vec![Rarity::SSR])
+                } else if shown.soft == 1.0 {
+                    Some(vec![Rarity::SSR, Rarity::SR])
+                } else {
+                    guaranteed.clone().filter(|_| kind == PityKind::SoftCurve)
+                };
+                assert_eq!(guaranteed, expected, "{kind} {count}");
+            }
+        }
+    }
+
+    #[test]
+    fn parse_kind() {
+        for kind in PityKind::ALL {
+            assert_eq!(kind.to_string().parse::<PityKind>().unwrap(), kind);
+        }
+        assert!("soft".parse::<PityKind>().is_err());
+    }
+}
@@ -0,0 +1,324 @@
+use gdnative::prelude::*;
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    seq::SliceRandom,
+    Rng,
+};
+
+use crate::rng::{self, RngStreams};
+
+/// Attempts per requested room before `place_rooms` gives up on fitting more.
+const ROOM_ATTEMPTS: u32 = 30;
+
+/// Seeded noise, scattering and room layout helpers for level generation.
+///
+/// Everything is derived from the seed set with `set_seed`, so the same seed and the same
+/// sequence of calls always generate the same level. Noise doesn't consume randomness and
+/// can be sampled in any order.
+#[derive(NativeClass)]
+#[inherit(Reference)]
+pub struct ProcGen {
+    streams: RngStreams,
+    /// Doubled permutation table shared by the Perlin and simplex noise.
+    perm: Vec<u8>,
+}
+
+#[methods]
+impl ProcGen {
+    fn new(_owner: &Reference) -> Self {
+        Self::with_streams(RngStreams::from_entropy())
+    }
+
+    fn with_streams(mut streams: RngStreams) -> Self {
+        let mut perm: Vec<u8> = (0..=255).collect();
+        perm.shuffle(streams.stream(rng::NOISE));
+        perm.extend_from_within(..);
+        ProcGen { streams, perm }
+    }
+
+    /// Restart generation from `seed`.
+    #[method]
+    fn set_seed(&mut self, seed: i64) {
+        *self = Self::with_streams(RngStreams::new(seed as u64));
+    }
+
+    /// 2D Perlin noise at `(x, y)`, roughly in `-1..=1` and `0` on integer coordinates.
+    #[method]
+    fn perlin_2d(&self, x: f64, y: f64) -> f64 {
+        let (xi, yi) = (x.floor(), y.floor());
+        let (xf, yf) = (x - xi, y - yi);
+        let (u, v) = (fade(xf), fade(yf));
+        let corner = |dx: f64, dy: f64| {
+            let hash = self.hash(xi as i64 + dx as i64, yi as i64 + dy as i64);
+            gradient(hash, xf - dx, yf - dy)
+        };
+        lerp(
+            v,
+            lerp(u, corner(0.0, 0.0), corner(1.0, 0.0)),
+            lerp(u, corner(0.0, 1.0), corner(1.0, 1.0)),
+        )
+    }
+
+    /// 2D simplex noise at `(x, y)`, in `-1..=1`.
+    #[method]
+    fn simplex_2d(&self, x: f64, y: f64) -> f64 {
+        let f2 = 0.5 * (3f64.sqrt() - 1.0);
+        let g2 = (3.0 - 3f64.sqrt()) / 6.0;
+
+        // skew into the simplex grid to find the cell, then unskew back
+        let skew = (x + y) * f2;
+        let (i, j) = ((x + skew).floor(), (y + skew).floor());
+        let unskew = (i + j) * g2;
+        let (x0, y0) = (x - (i - unskew), y - (j - unskew));
+        // which of the two triangles of the cell we're in
+        let (i1, j1) = if x0 > y0 { (1.0, 0.0) } else { (0.0, 1.0) };
+        let corners = [
+            (0.0, 0.0, x0, y0),
+            (i1, j1, x0 - i1 + g2, y0 - j1 + g2),
+            (1.0, 1.0, x0 - 1.0 + 2.0 * g2, y0 - 1.0 + 2.0 * g2),
+        ];
+
+        let total: f64 = corners
+            .iter()
+            .map(|&(di, dj, cx, cy)| {
+                let t = 0.5 - cx * cx - cy * cy;
+                if t < 0.0 {
+                    return 0.0;
+                }
+                let hash = self.hash((i + di) as i64, (j + dj) as i64);
+                t.powi(4) * gradient(hash, cx, cy)
+            })
+            .sum();
+        // scales the result into -1..=1
+        70.0 * total
+    }
+
+    /// Fractal Perlin noise: `octaves` layers, each `lacunarity` times the frequency and
+    /// `persistence` times the amplitude of the previous one, normalized into `-1..=1`.
+    #[method]
+    fn fractal_2d(&self, x: f64, y: f64, octaves: u32, persistence: f64, lacunarity: f64) -> f64 {
+        let (mut frequency, mut amplitude) = (1.0, 1.0);
+        let (mut total, mut max) = (0.0, 0.0);
+        for _ in 0..octaves.max(1) {
+            total += amplitude * self.perlin_2d(x * frequency, y * frequency);
+            max += amplitude;
+            frequency *= lacunarity;
+            amplitude *= persistence;
+        }
+        total / max
+    }
+
+    /// `count` random points, each in one of `regions` picked proportionally to its weight.
+    ///
+    /// `regions` is an Array of `[Rect2, weight]` pairs. Returns no points if no region has
+    /// a positive weight.
+    #[method]
+    fn scatter(&mut self, regions: Vec<(Rect2, f64)>, count: u32) -> Vector2Array {
+        let weights = regions.iter().map(|(_, weight)| weight.max(0.0));
+        let Ok(dist) = WeightedIndex::new(weights) else {
+            godot_error!("scatter needs at least one region with a positive weight");
+            return Vector2Array::new();
+        };
+        let rng = self.streams.stream(rng::SCATTER);
+        (0..count)
+            .map(|_| {
+                let (region, _) = regions[dist.sample(rng)];
+                Vector2::new(
+                    region.position.x + rng.gen::<f32>() * region.size.x,
+                    region.position.y + rng.gen::<f32>() * region.size.y,
+                )
+            })
+            .collect()
+    }
+
+    /// Place up to `count` non-overlapping rooms inside `bounds`, at least `padding` apart.
+    ///
+    /// Room sizes are between `min_size` and `max_size`. Positions and sizes are whole
+    /// numbers so rooms line up with tile grids. Fewer rooms are returned when they don't fit,
+    /// none when the bounds or sizes are unusable.
+    #[method]
+    fn place_rooms(
+        &mut self,
+        bounds: Rect2,
+        count: u32,
+        min_size: Vector2,
+        max_size: Vector2,
+        padding: f32,
+    ) -> Vec<Rect2> {
+        if let Some(problem) = room_problem(bounds, min_size, max_size, padding) {
+            godot_error!("place_rooms: {problem}");
+            return vec![];
+        }
+        let rng = self.streams.stream(rng::ROOMS);
+        let mut rooms: Vec<Rect2> = vec![];
+        for _ in 0..count.saturating_mul(ROOM_ATTEMPTS) {
+            if rooms.len() == count as usize {
+                break;
+            }
+            let width = rng.gen_range(min_size.x..=max_size.x).round();
+            let height = rng.gen_range(min_size.y..=max_size.y).round();
+            let free_x = bounds.size.x - width;
+            let free_y = bounds.size.y - height;
+            if free_x < 0.0 || free_y < 0.0 {
+                continue;
+            }
+            let room = Rect2::from_components(
+                (bounds.position.x + rng.gen_range(0.0..=free_x)).floor(),
+                (bounds.position.y + rng.gen_range(0.0..=free_y)).floor(),
+                width,
+                height,
+            );
+            let padded = room.grow(padding);
+            if rooms.iter().all(|other| !padded.intersects(*other)) {
+                rooms.push(room);
+            }
+        }
+        rooms
+    }
+
+    fn hash(&self, x: i64, y: i64) -> u8 {
+        let x = (x & 255) as usize;
+        let y = (y & 255) as usize;
+        self.perm[self.perm[x] as usize + y]
+    }
+}
+
+/// Why `place_rooms` can't lay out rooms with these arguments, `None` if it can.
+///
+/// Checked up front since `gen_range` panics on NaN, infinite or inverted ranges.
+fn room_problem(
+    bounds: Rect2,
+    min_size: Vector2,
+    max_size: Vector2,
+    padding: f32,
+) -> Option<String> {
+    let values = [
+        bounds.position.x,
+        bounds.position.y,
+        bounds.size.x,
+        bounds.size.y,
+        min_size.x,
+        min_size.y,
+        max_size.x,
+        max_size.y,
+        padding,
+    ];
+    if values.iter().any(|value| !value.is_finite()) {
+        return Some("bounds, sizes and padding have to be finite numbers".into());
+    }
+    if min_size.x < 0.0 || min_size.y < 0.0 {
+        return Some(format!("min_size {min_size:?} is negative"));
+    }
+    if min_size.x > max_size.x || min_size.y > max_size.y {
+        return Some(format!(
+            "min_size {min_size:?} is larger than max_size {max_size:?}"
+        ));
+    }
+    None
+}
+
+/// Perlin's smootherstep, `6t^5 - 15t^4 + 10t^3`.
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+/// Dot product of `(x, y)` with one of 8 gradient directions picked by `hash`.
+fn gradient(hash: u8, x: f64, y: f64) -> f64 {
+    match hash & 7 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{room_problem, ProcGen};
+    use crate::rng::RngStreams;
+    use gdnative::prelude::*;
+
+    fn proc_gen(seed: u64) -> ProcGen {
+        ProcGen::with_streams(RngStreams::new(seed))
+    }
+
+    #[test]
+    fn noise() {
+        let (a, b) = (proc_gen(458), proc_gen(459));
+        let mut differs = false;
+        for i in 0..400 {
+            let (x, y) = (f64::from(i) * 0.37, f64::from(i) * 0.61);
+            for value in [
+                a.perlin_2d(x, y),
+                a.simplex_2d(x, y),
+                a.fractal_2d(x, y, 4, 0.5, 2.0),
+            ] {
+                assert!((-1.0..=1.0).contains(&value), "{value} at {x}, {y}");
+            }
+            assert_eq!(a.perlin_2d(x, y), proc_gen(458).perlin_2d(x, y));
+            differs |= a.simplex_2d(x, y) != b.simplex_2d(x, y);
+        }
+        assert!(differs);
+        assert_eq!(a.perlin_2d(3.0, -7.0), 0.0);
+    }
+
+    #[test]
+    fn scatter_by_weight() {
+        let left = Rect2::from_components(0.0, 0.0, 10.0, 10.0);
+        let right = Rect2::from_components(100.0, 0.0, 10.0, 10.0);
+        let mut gen = proc_gen(458);
+
+        let points = gen.scatter(vec![(left, 1.0), (right, 0.0)], 50).to_vec();
+        assert_eq!(points.len(), 50);
+        assert!(points.iter().all(|p| p.x < 10.0 && p.y < 10.0));
+        assert!(gen.scatter(vec![(left, 0.0)], 5).is_empty());
+    }
+
+    #[test]
+    fn rooms_fit_without_overlap() {
+        let bounds = Rect2::from_components(0.0, 0.0, 64.0, 48.0);
+        let place = |seed| {
+            proc_gen(seed).place_rooms(
+                bounds,
+                12,
+                Vector2::new(4.0, 4.0),
+                Vector2::new(10.0, 8.0),
+                1.0,
+            )
+        };
+
+        let rooms = place(458);
+        assert!(!rooms.is_empty());
+        assert_eq!(rooms, place(458));
+        for (idx, room) in rooms.iter().enumerate() {
+            assert!(room.position.x >= 0.0 && room.position.y >= 0.0);
+            assert!(room.end().x <= 64.0 && room.end().y <= 48.0);
+            assert_eq!(room.size.x, room.size.x.round());
+            for other in &rooms[idx + 1..] {
+                assert!(!room.grow(1.0).intersects(*other));
+            }
+        }
+    }
+
+    #[test]
+    fn unusable_room_sizes() {
+        let bounds = Rect2::from_components(0.0, 0.0, 64.0, 48.0);
+        let (small, large) = (Vector2::new(4.0, 4.0), Vector2::new(10.0, 8.0));
+        assert_eq!(room_problem(bounds, small, large, 1.0), None);
+        assert!(room_problem(bounds, large, small, 1.0).is_some());
+        assert!(room_problem(bounds, Vector2::new(f32::NAN, 4.0), large, 1.0).is_some());
+        assert!(room_problem(bounds, small, Vector2::new(f32::INFINITY, 8.0), 1.0).is_some());
+        assert!(room_problem(bounds, small, large, f32::NAN).is_some());
+
+        let mut gen = proc_gen(458);
+        assert!(gen.place_rooms(bounds, 4, large, small, 1.0).is_empty());
+    }
+}
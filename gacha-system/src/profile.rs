@@ -0,0 +1,83 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, PoisonError, RwLock},
+};
+
+use crate::{gacha_core::GachaItem, limits::SpendTracker};
+
+/// In-memory gacha counters of a profile, the state a scene change must not reset.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProfileCounters {
+    pub chances: u32,
+    pub pity_count: u32,
+    pub hard_pity_count: u32,
+    pub spend: SpendTracker,
+    pub session_pulls: u32,
+    pub featured_guarantee: bool,
+    /// Scripted results not handed out yet, so each is consumed once per profile.
+    pub scripted_results: Vec<GachaItem>,
+    /// Pending `confirm_spend` token.
+    pub spend_token: Option<String>,
+    /// Token `reset_profile` expects.
+    pub reset_token: Option<String>,
+    /// Counters of the banners besides the standard one.
+    pub banners: BTreeMap<String, BannerCounters>,
+}
+
+/// Pity progress of one banner.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BannerCounters {
+    pub pity_count: u32,
+    pub hard_pity_count: u32,
+    pub featured_guarantee: bool,
+}
+
+/// Counters shared by every `GachaSystem` node of a profile, `None` until the first node
+/// of the profile is ready.
+#[derive(Debug, Clone, Default)]
+pub struct SharedCounters(Arc<RwLock<Option<ProfileCounters>>>);
+
+impl SharedCounters {
+    pub fn get(&self) -> Option<ProfileCounters> {
+        // counters are plain values, a panic mid-write can't leave them half updated
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn set(&self, counters: ProfileCounters) {
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = Some(counters);
+    }
+}
+
+static PROFILES: Mutex<BTreeMap<String, SharedCounters>> = Mutex::new(BTreeMap::new());
+
+/// Handle on the counters of `profile`, the same one for every caller for the lifetime of the
+/// process.
+pub fn shared_counters(profile: &str) -> SharedCounters {
+    PROFILES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(profile.to_string())
+        .or_default()
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{shared_counters, ProfileCounters};
+
+    #[test]
+    fn handles_per_profile() {
+        let first = shared_counters("handles_per_profile");
+        assert_eq!(first.get(), None);
+        let counters = ProfileCounters {
+            pity_count: 7,
+            ..Default::default()
+        };
+        first.set(counters.clone());
+        assert_eq!(shared_counters("handles_per_profile").get(), Some(counters));
+        assert_eq!(shared_counters("handles_per_profile_other").get(), None);
+    }
+}
@@ -0,0 +1,94 @@
+use gdnative::prelude::*;
+
+use crate::{
+    gacha_core::{GachaItem, Rarity},
+    reveal::RevealPlan,
+};
+
+/// Format version of [`Recording`], bumped whenever its fields change.
+pub const RECORDING_VERSION: u32 = 6;
+
+/// A replayable gacha session, from `start_recording` until `stop_recording`.
+///
+/// Holds the RNG seed and the state at the start of the session along with every pull made,
+/// so QA can attach it to a bug report and `play_recording` reproduces it exactly.
+#[derive(Debug, Clone, ToVariant, FromVariant)]
+pub struct Recording {
+    pub version: u32,
+    /// Hash of the pools, rates and strategies in use, see `GachaSystem::config_hash`.
+    pub config_hash: String,
+    pub seed: u64,
+    /// Seed already committed to for the next verifiable pull, see
+    /// `GachaSystem::get_fairness_commitment`.
+    pub fairness_seed: Option<u64>,
+    pub chances: u32,
+    pub pity_count: u32,
+    pub hard_pity_count: u32,
+    pub featured_guarantee: bool,
+    pub scripted_results: Vec<GachaItem>,
+    /// Items left in each rarity's box, empty unless the `box` sampler is used.
+    pub box_state: Vec<(Rarity, Vec<usize>)>,
+    /// State of the banners besides the standard one.
+    pub banners: Vec<BannerState>,
+    pub commands: Vec<RecordedCommand>,
+}
+
+/// Pity progress and box contents of a banner at the start of a [`Recording`].
+#[derive(Debug, Clone, ToVariant, FromVariant)]
+pub struct BannerState {
+    pub id: String,
+    pub pity_count: u32,
+    pub hard_pity_count: u32,
+    pub featured_guarantee: bool,
+    pub box_state: Vec<(Rarity, Vec<usize>)>,
+}
+
+/// A single `pull` or `pull_with_reveal` call and what it returned.
+#[derive(Debug, Clone, PartialEq, ToVariant, FromVariant)]
+pub struct RecordedCommand {
+    /// `"pull"` or `"pull_with_reveal"`.
+    pub command: String,
+    pub banner: String,
+    pub num: u32,
+    /// Names of the pulled items, in order.
+    pub items: Vec<String>,
+    pub reveal: Option<RevealPlan>,
+}
+
+impl RecordedCommand {
+    pub fn new(
+        banner: &str,
+        num: u32,
+        items: &[GachaItem],
+        reveal: Option<RevealPlan>,
+        with_reveal: bool,
+    ) -> Self {
+        RecordedCommand {
+            command: if with_reveal {
+                "pull_with_reveal"
+            } else {
+                "pull"
+            }
+            .to_string(),
+            banner: banner.to_string(),
+            num,
+            items: items.iter().map(|item| item.name.clone()).collect(),
+            reveal,
+        }
+    }
+
+    pub fn with_reveal(&self) -> bool {
+        self.command == "pull_with_reveal"
+    }
+}
+
+/// Outcome of replaying a [`Recording`].
+#[derive(Debug, Clone, Default, ToVariant)]
+pub struct ReplayReport {
+    /// Whether the node was configured like the recorded one, results can't match otherwise.
+    pub config_matches: bool,
+    /// Index of the first command whose results differ, `None` if the replay matched.
+    pub diverged_at: Option<usize>,
+    pub expected: Option<RecordedCommand>,
+    pub actual: Option<RecordedCommand>,
+}
@@ -0,0 +1,99 @@
+use gdnative::prelude::*;
+use rand::Rng;
+
+use crate::gacha_core::{GachaItem, Rarity};
+
+/// How the reveal scene should present a batch of pull results.
+///
+/// When `upgrade_index` is set, the batch is first presented as `initial` and
+/// upgrades to `actual` once the item at that index is revealed. The tease only
+/// ever under-promises, so nothing better than the real outcome is shown early.
+#[derive(Debug, ToVariant, FromVariant, Clone, PartialEq, Eq)]
+pub struct RevealPlan {
+    pub initial: Rarity,
+    pub actual: Rarity,
+    pub upgrade_index: Option<usize>,
+}
+
+/// Decide whether to tease an upgrade for `items`, returns `None` for an empty batch.
+///
+/// Only batches containing an SR or SSR are eligible, they present as one rarity lower
+/// with a chance of `tease_chance`.
+pub fn plan_reveal<R: Rng + ?Sized>(
+    items: &[GachaItem],
+    tease_chance: f64,
+    rng: &mut R,
+) -> Option<RevealPlan> {
+    // rarities are ordered from best to worst
+    let (best_index, best) = items
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, item)| item.rarity)
+        .map(|(idx, item)| (idx, item.rarity))?;
+    let teased = match best {
+        Rarity::SSR => Some(Rarity::SR),
+        Rarity::SR => Some(Rarity::R),
+        _ => None,
+    }
+    .filter(|_| tease_chance > 0.0 && rng.gen_bool(tease_chance.min(1.0)));
+
+    Some(match teased {
+        Some(initial) => RevealPlan {
+            initial,
+            actual: best,
+            upgrade_index: Some(best_index),
+        },
+        None => RevealPlan {
+            initial: best,
+            actual: best,
+            upgrade_index: None,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{plan_reveal, RevealPlan};
+    use crate::gacha_core::{GachaItem, Rarity};
+
+    fn batch(rarities: &[Rarity]) -> Vec<GachaItem> {
+        rarities
+            .iter()
+            .map(|rarity| GachaItem {
+                name: format!("{rarity:?}"),
+                rarity: *rarity,
+                weight: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tease_rules() {
+        let mut rng = rand::thread_rng();
+        let items = batch(&[Rarity::N, Rarity::R, Rarity::SSR, Rarity::SR]);
+
+        assert_eq!(
+            plan_reveal(&items, 0.0, &mut rng),
+            Some(RevealPlan {
+                initial: Rarity::SSR,
+                actual: Rarity::SSR,
+                upgrade_index: None,
+            })
+        );
+        assert_eq!(
+            plan_reveal(&items, 1.0, &mut rng),
+            Some(RevealPlan {
+                initial: Rarity::SR,
+                actual: Rarity::SSR,
+                upgrade_index: Some(2),
+            })
+        );
+        // nothing worth teasing
+        let commons = batch(&[Rarity::N, Rarity::R]);
+        assert_eq!(
+            plan_reveal(&commons, 1.0, &mut rng).and_then(|plan| plan.upgrade_index),
+            None
+        );
+        assert_eq!(plan_reveal(&[], 1.0, &mut rng), None);
+    }
+}
@@ -0,0 +1,217 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    Rng, RngCore,
+};
+
+use crate::{
+    error::{GachaError, Result},
+    gacha_core::{GachaItem, Rarity},
+};
+
+/// How an item is picked out of the pool of the rarity that was rolled.
+///
+/// Runs after the pity strategy decided the rarity, so the two can be combined freely.
+pub(crate) trait Sampler {
+    /// Index into `pool` of the item to hand out, `None` if nothing can be picked.
+    fn pick(&mut self, rarity: Rarity, pool: &[GachaItem], rng: &mut dyn RngCore) -> Option<usize>;
+
+    /// Forget any state kept about the pools, called when they change.
+    fn reset(&mut self) {}
+}
+
+/// Every item of the rarity is equally likely.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Uniform;
+
+impl Sampler for Uniform {
+    fn pick(&mut self, _: Rarity, pool: &[GachaItem], rng: &mut dyn RngCore) -> Option<usize> {
+        (!pool.is_empty()).then(|| rng.gen_range(0..pool.len()))
+    }
+}
+
+/// Items are picked proportionally to their `weight`, items without one weigh `1.0`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Weighted;
+
+impl Sampler for Weighted {
+    fn pick(&mut self, _: Rarity, pool: &[GachaItem], rng: &mut dyn RngCore) -> Option<usize> {
+        pick_weighted(pool.iter().map(|item| item.weight.unwrap_or(1.0)), rng)
+    }
+}
+
+/// Index of an entry picked proportionally to `weights`, `None` if there are none or every
+/// weight is `0`. Shared by everything rolling weighted tables, e.g. encounters and events.
+pub(crate) fn pick_weighted(
+    weights: impl IntoIterator<Item = f64>,
+    rng: &mut dyn RngCore,
+) -> Option<usize> {
+    WeightedIndex::new(weights)
+        .ok()
+        .map(|dist| dist.sample(rng))
+}
+
+/// Box gacha: items are drawn without replacement, a rarity's box is refilled once
+/// every item of it was drawn.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BoxSampler {
+    /// Indices of the items still in the box, per rarity.
+    remaining: BTreeMap<Rarity, Vec<usize>>,
+}
+
+impl Sampler for BoxSampler {
+    fn pick(&mut self, rarity: Rarity, pool: &[GachaItem], rng: &mut dyn RngCore) -> Option<usize> {
+        if pool.is_empty() {
+            return None;
+        }
+        let remaining = self.remaining.entry(rarity).or_default();
+        if remaining.is_empty() {
+            remaining.extend(0..pool.len());
+        }
+        Some(remaining.swap_remove(rng.gen_range(0..remaining.len())))
+    }
+
+    fn reset(&mut self) {
+        self.remaining.clear();
+    }
+}
+
+/// Sampler selected by the `item_sampler` property, along with its state.
+#[derive(Debug, Clone)]
+pub(crate) enum ItemSampler {
+    Uniform(Uniform),
+    Weighted(Weighted),
+    Box(BoxSampler),
+}
+
+impl Default for ItemSampler {
+    fn default() -> Self {
+        ItemSampler::Uniform(Uniform)
+    }
+}
+
+impl ItemSampler {
+    pub const NAMES: [&'static str; 3] = ["uniform", "weighted", "box"];
+
+    pub fn as_sampler(&mut self) -> &mut dyn Sampler {
+        match self {
+            ItemSampler::Uniform(sampler) => sampler,
+            ItemSampler::Weighted(sampler) => sampler,
+            ItemSampler::Box(sampler) => sampler,
+        }
+    }
+
+    /// Items left in each rarity's box, empty for the other samplers.
+    pub fn box_state(&self) -> Vec<(Rarity, Vec<usize>)> {
+        match self {
+            ItemSampler::Box(sampler) => sampler
+                .remaining
+                .iter()
+                .map(|(rarity, remaining)| (*rarity, remaining.clone()))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The same kind of sampler with fresh state, e.g. a full box.
+    pub fn fresh(&self) -> Self {
+        match self {
+            ItemSampler::Box(_) => ItemSampler::Box(BoxSampler::default()),
+            other => other.clone(),
+        }
+    }
+
+    pub fn restore_box_state(&mut self, state: &[(Rarity, Vec<usize>)]) {
+        if let ItemSampler::Box(sampler) = self {
+            sampler.remaining = state.iter().cloned().collect();
+        }
+    }
+}
+
+impl fmt::Display for ItemSampler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ItemSampler::Uniform(_) => "uniform",
+            ItemSampler::Weighted(_) => "weighted",
+            ItemSampler::Box(_) => "box",
+        })
+    }
+}
+
+impl FromStr for ItemSampler {
+    type Err = GachaError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "uniform" => Ok(ItemSampler::Uniform(Uniform)),
+            "weighted" => Ok(ItemSampler::Weighted(Weighted)),
+            "box" => Ok(ItemSampler::Box(BoxSampler::default())),
+            _ => Err(GachaError::InvalidProperty(
+                "item_sampler".to_string(),
+                format!(
+                    "unknown sampler \"{s}\", expected one of {}",
+                    ItemSampler::NAMES.join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BoxSampler, ItemSampler, Sampler, Weighted};
+    use crate::gacha_core::{GachaItem, Rarity};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn pool(weights: &[Option<f64>]) -> Vec<GachaItem> {
+        weights
+            .iter()
+            .enumerate()
+            .map(|(idx, weight)| GachaItem {
+                name: format!("item-{idx}"),
+                rarity: Rarity::R,
+                weight: *weight,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn weighted_skips_zero_weights() {
+        let mut rng = StdRng::seed_from_u64(447);
+        let items = pool(&[Some(0.0), None, Some(3.0)]);
+        let mut counts = [0; 3];
+        for _ in 0..4000 {
+            counts[Weighted.pick(Rarity::R, &items, &mut rng).unwrap()] += 1;
+        }
+        assert_eq!(counts[0], 0);
+        assert!(counts[2] > counts[1] * 2, "{counts:?}");
+        assert_eq!(
+            Weighted.pick(Rarity::R, &pool(&[Some(0.0)]), &mut rng),
+            None
+        );
+    }
+
+    #[test]
+    fn box_draws_without_replacement() {
+        let mut rng = StdRng::seed_from_u64(447);
+        let items = pool(&[None; 5]);
+        let mut sampler = BoxSampler::default();
+        for _ in 0..3 {
+            let mut drawn: Vec<_> = (0..5)
+                .map(|_| sampler.pick(Rarity::R, &items, &mut rng).unwrap())
+                .collect();
+            drawn.sort_unstable();
+            assert_eq!(drawn, [0, 1, 2, 3, 4]);
+        }
+        assert_eq!(sampler.pick(Rarity::SSR, &[], &mut rng), None);
+    }
+
+    #[test]
+    fn parse_sampler() {
+        for name in ItemSampler::NAMES {
+            assert_eq!(name.parse::<ItemSampler>().unwrap().to_string(), name);
+        }
+        assert!("random".parse::<ItemSampler>().is_err());
+    }
+}
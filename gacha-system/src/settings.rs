@@ -0,0 +1,304 @@
+use gdnative::api::ConfigFile;
+use gdnative::core_types::GodotError;
+use gdnative::export::Export;
+use gdnative::prelude::*;
+
+/// Player options, kept in one place for the UI and the native subsystems.
+#[derive(Debug, Clone, PartialEq)]
+struct Options {
+    master_volume: f64,
+    music_volume: f64,
+    sfx_volume: f64,
+    locale: String,
+    reduce_motion: bool,
+    high_contrast: bool,
+    text_scale: f64,
+    confirm_pulls: bool,
+    confirm_purchases: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            master_volume: 1.0,
+            music_volume: 0.8,
+            sfx_volume: 0.8,
+            locale: "en".into(),
+            reduce_motion: false,
+            high_contrast: false,
+            text_scale: 1.0,
+            confirm_pulls: true,
+            confirm_purchases: true,
+        }
+    }
+}
+
+/// Ties an option to its exported property and its `[section] key` in the settings file.
+struct Binding<T> {
+    section: &'static str,
+    key: &'static str,
+    get: fn(&Options) -> T,
+    /// Stores a new value, clamping it into the valid range and ignoring unusable ones.
+    set: fn(&mut Options, T),
+}
+
+/// Store `value` clamped to `min..=max`, keeping the current value for NaN and infinities.
+fn set_in_range(option: &mut f64, value: f64, min: f64, max: f64) {
+    if value.is_finite() {
+        *option = value.clamp(min, max);
+    }
+}
+
+const FLOATS: [Binding<f64>; 4] = [
+    Binding {
+        section: "audio",
+        key: "master_volume",
+        get: |o| o.master_volume,
+        set: |o, v| set_in_range(&mut o.master_volume, v, 0.0, 1.0),
+    },
+    Binding {
+        section: "audio",
+        key: "music_volume",
+        get: |o| o.music_volume,
+        set: |o, v| set_in_range(&mut o.music_volume, v, 0.0, 1.0),
+    },
+    Binding {
+        section: "audio",
+        key: "sfx_volume",
+        get: |o| o.sfx_volume,
+        set: |o, v| set_in_range(&mut o.sfx_volume, v, 0.0, 1.0),
+    },
+    Binding {
+        section: "accessibility",
+        key: "text_scale",
+        get: |o| o.text_scale,
+        set: |o, v| set_in_range(&mut o.text_scale, v, 0.5, 2.0),
+    },
+];
+
+const BOOLS: [Binding<bool>; 4] = [
+    Binding {
+        section: "accessibility",
+        key: "reduce_motion",
+        get: |o| o.reduce_motion,
+        set: |o, v| o.reduce_motion = v,
+    },
+    Binding {
+        section: "accessibility",
+        key: "high_contrast",
+        get: |o| o.high_contrast,
+        set: |o, v| o.high_contrast = v,
+    },
+    Binding {
+        section: "confirmations",
+        key: "confirm_pulls",
+        get: |o| o.confirm_pulls,
+        set: |o, v| o.confirm_pulls = v,
+    },
+    Binding {
+        section: "confirmations",
+        key: "confirm_purchases",
+        get: |o| o.confirm_purchases,
+        set: |o, v| o.confirm_purchases = v,
+    },
+];
+
+const STRINGS: [Binding<String>; 1] = [Binding {
+    section: "general",
+    key: "locale",
+    get: |o| o.locale.clone(),
+    set: |o, v| {
+        if !v.is_empty() {
+            o.locale = v;
+        }
+    },
+}];
+
+/// Options store, meant to be an autoload the UI binds its options menu to.
+///
+/// Every option is an exported property (`Settings.music_volume = 0.5`), out of range values
+/// are clamped and NaN or infinite ones ignored. Once `_ready` has loaded `save_path`, each change is saved right away and
+/// emits `setting_changed`.
+#[derive(NativeClass, Debug, Default)]
+#[inherit(Node)]
+#[register_with(Self::register)]
+pub struct Settings {
+    /// ConfigFile the options are kept in.
+    #[property]
+    save_path: String,
+    options: Options,
+    /// Set once the saved options are loaded, changes before that are scene defaults.
+    _loaded: bool,
+}
+
+#[methods]
+impl Settings {
+    fn new(_owner: &Node) -> Self {
+        Settings {
+            save_path: "user://settings.cfg".into(),
+            ..Default::default()
+        }
+    }
+
+    fn register(builder: &ClassBuilder<Self>) {
+        builder
+            .signal("setting_changed")
+            .with_param("name", VariantType::GodotString)
+            .with_param("value", VariantType::Nil)
+            .done();
+
+        register_options(builder, &FLOATS);
+        register_options(builder, &BOOLS);
+        register_options(builder, &STRINGS);
+    }
+
+    #[method]
+    fn _ready(&mut self) {
+        self.load_settings();
+        self._loaded = true;
+    }
+
+    /// Put every option back to its default, emitting `setting_changed` for the ones that change.
+    #[method]
+    fn reset_to_defaults(&mut self, #[base] owner: TRef<Node>) {
+        let defaults = Options::default();
+        self.reset(owner, &FLOATS, &defaults);
+        self.reset(owner, &BOOLS, &defaults);
+        self.reset(owner, &STRINGS, &defaults);
+    }
+
+    fn reset<T: ToVariant + PartialEq>(
+        &mut self,
+        owner: TRef<Node>,
+        bindings: &[Binding<T>],
+        defaults: &Options,
+    ) {
+        for binding in bindings {
+            self.update(owner, binding, (binding.get)(defaults));
+        }
+    }
+
+    fn update<T: ToVariant + PartialEq>(
+        &mut self,
+        owner: TRef<Node>,
+        binding: &Binding<T>,
+        value: T,
+    ) {
+        let before = (binding.get)(&self.options);
+        (binding.set)(&mut self.options, value);
+        let after = (binding.get)(&self.options);
+        if self._loaded && after != before {
+            self.save_settings();
+            owner.emit_signal(
+                "setting_changed",
+                &[binding.key.to_variant(), after.to_variant()],
+            );
+        }
+    }
+
+    fn save_settings(&self) {
+        let file = ConfigFile::new();
+        save_options(&file, &self.options, &FLOATS);
+        save_options(&file, &self.options, &BOOLS);
+        save_options(&file, &self.options, &STRINGS);
+        if let Err(e) = file.save(self.save_path.as_str()) {
+            godot_error!("could not save settings to {}: {e}", self.save_path);
+        }
+    }
+
+    fn load_settings(&mut self) {
+        let file = ConfigFile::new();
+        match file.load(self.save_path.as_str()) {
+            Ok(()) => {}
+            Err(GodotError::FileNotFound) => return,
+            Err(e) => {
+                godot_error!("could not load settings from {}: {e}", self.save_path);
+                return;
+            }
+        }
+        load_options(&file, &mut self.options, &FLOATS);
+        load_options(&file, &mut self.options, &BOOLS);
+        load_options(&file, &mut self.options, &STRINGS);
+    }
+}
+
+fn register_options<T: Export + FromVariant + PartialEq>(
+    builder: &ClassBuilder<Settings>,
+    bindings: &'static [Binding<T>],
+) {
+    for binding in bindings {
+        builder
+            .property::<T>(binding.key)
+            .with_default((binding.get)(&Options::default()))
+            .with_getter(move |this: &Settings, _| (binding.get)(&this.options))
+            .with_setter(move |this: &mut Settings, owner, value: T| {
+                this.update(owner, binding, value)
+            })
+            .done();
+    }
+}
+
+fn save_options<T: ToVariant>(file: &ConfigFile, options: &Options, bindings: &[Binding<T>]) {
+    for binding in bindings {
+        file.set_value(
+            binding.section,
+            binding.key,
+            (binding.get)(options).to_variant(),
+        );
+    }
+}
+
+/// Apply the saved options, keeping the current value of missing or mistyped ones.
+fn load_options<T: FromVariant>(file: &ConfigFile, options: &mut Options, bindings: &[Binding<T>]) {
+    for binding in bindings {
+        if !file.has_section_key(binding.section, binding.key) {
+            continue;
+        }
+        let value = file.get_value(binding.section, binding.key, Variant::nil());
+        match value.try_to::<T>() {
+            Ok(value) => (binding.set)(options, value),
+            Err(e) => godot_warn!("ignored saved setting {}: {e}", binding.key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Options, BOOLS, FLOATS, STRINGS};
+
+    #[test]
+    fn clamped_options() {
+        let mut options = Options::default();
+        for binding in &FLOATS {
+            (binding.set)(&mut options, 5.0);
+            assert!((binding.get)(&options) <= 2.0, "{}", binding.key);
+            (binding.set)(&mut options, -1.0);
+            assert!((binding.get)(&options) >= 0.0, "{}", binding.key);
+        }
+        assert_eq!(options.master_volume, 0.0);
+        assert_eq!(options.text_scale, 0.5);
+        // NaN would never compare equal to itself, and get saved and signalled on every set
+        for binding in &FLOATS {
+            (binding.set)(&mut options, f64::NAN);
+            (binding.set)(&mut options, f64::INFINITY);
+        }
+        assert_eq!(options.master_volume, 0.0);
+        assert_eq!(options.text_scale, 0.5);
+
+        (STRINGS[0].set)(&mut options, "".into());
+        assert_eq!(options.locale, "en");
+        (STRINGS[0].set)(&mut options, "ja".into());
+        assert_eq!(options.locale, "ja");
+    }
+
+    #[test]
+    fn unique_keys() {
+        let mut keys: Vec<_> = FLOATS.iter().map(|b| b.key).collect();
+        keys.extend(BOOLS.iter().map(|b| b.key));
+        keys.extend(STRINGS.iter().map(|b| b.key));
+        let count = keys.len();
+        keys.sort_unstable();
+        keys.dedup();
+        assert_eq!(keys.len(), count);
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+
+use gdnative::api::ConfigFile;
+use gdnative::prelude::*;
+
+use crate::{
+    error::{GachaError, Result},
+    gacha_core::{GachaItem, Rarity},
+    limits::SpendTracker,
+};
+
+/// Schema version of saved gacha state, bumped whenever its layout changes.
+///
+/// Older saves still load, keys they don't have keep the node's current values.
+pub const STATE_VERSION: u32 = 1;
+
+const META: &str = "meta";
+const GACHA: &str = "gacha";
+/// Prefix of the per-banner sections, e.g. `banner.standard`.
+const BANNER: &str = "banner.";
+
+/// Persistent gacha state as read from or written to a save, `None` for keys a save lacks.
+///
+/// Banner pools and rates aren't saved, they come from the game data so content updates
+/// apply to existing saves.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SavedState {
+    /// `config_hash` of the node that saved, box contents are only restored when it matches.
+    pub config_hash: Option<String>,
+    pub chances: Option<u32>,
+    pub scripted_results: Option<Vec<GachaItem>>,
+    pub spend: Option<SpendTracker>,
+    pub banners: BTreeMap<String, SavedBanner>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SavedBanner {
+    pub pity_count: Option<u32>,
+    pub hard_pity_count: Option<u32>,
+    pub featured_guarantee: Option<bool>,
+    pub box_state: Option<Vec<(Rarity, Vec<usize>)>>,
+}
+
+impl SavedState {
+    pub fn write(&self, file: &ConfigFile) {
+        file.set_value(META, "version", STATE_VERSION);
+        set(file, META, "config_hash", &self.config_hash);
+        set(file, GACHA, "chances", &self.chances);
+        set(file, GACHA, "scripted_results", &self.scripted_results);
+        set(file, GACHA, "spend", &self.spend);
+        for (id, banner) in &self.banners {
+            let section = format!("{BANNER}{id}");
+            set(file, &section, "pity_count", &banner.pity_count);
+            set(file, &section, "hard_pity_count", &banner.hard_pity_count);
+            set(
+                file,
+                &section,
+                "featured_guarantee",
+                &banner.featured_guarantee,
+            );
+            set(file, &section, "box_state", &banner.box_state);
+        }
+    }
+
+    /// Read a loaded save, refusing saves from a newer schema than this build knows.
+    pub fn read(file: &ConfigFile) -> Result<Self> {
+        let version = get::<u32>(file, META, "version").unwrap_or(0);
+        if version > STATE_VERSION {
+            return Err(GachaError::UnsupportedSaveVersion(version));
+        }
+        let mut state = SavedState {
+            config_hash: get(file, META, "config_hash"),
+            chances: get(file, GACHA, "chances"),
+            scripted_results: get(file, GACHA, "scripted_results"),
+            spend: get(file, GACHA, "spend"),
+            banners: BTreeMap::new(),
+        };
+        for section in file.get_sections().to_vec() {
+            let section = section.to_string();
+            let Some(id) = section.strip_prefix(BANNER) else {
+                continue;
+            };
+            let banner = SavedBanner {
+                pity_count: get(file, &section, "pity_count"),
+                hard_pity_count: get(file, &section, "hard_pity_count"),
+                featured_guarantee: get(file, &section, "featured_guarantee"),
+                box_state: get(file, &section, "box_state"),
+            };
+            state.banners.insert(id.to_string(), banner);
+        }
+        Ok(state)
+    }
+}
+
+fn set<T: ToVariant>(file: &ConfigFile, section: &str, key: &str, value: &Option<T>) {
+    if let Some(value) = value {
+        file.set_value(section, key, value.to_variant());
+    }
+}
+
+/// Value of `section/key`, `None` if it's missing or of the wrong type.
+fn get<T: FromVariant>(file: &ConfigFile, section: &str, key: &str) -> Option<T> {
+    if !file.has_section_key(section, key) {
+        return None;
+    }
+    let value = file.get_value(section, key, Variant::nil());
+    T::from_variant(&value)
+        .map_err(|e| godot_warn!("ignored saved {section}/{key}: {e}"))
+        .ok()
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+use gdnative::api::{ConfigFile, File};
+use gdnative::core_types::GodotError;
+use gdnative::prelude::*;
+
+/// How much of the save one subsystem takes up.
+#[derive(Debug, Clone, PartialEq, ToVariant)]
+struct SaveUsage {
+    subsystem: String,
+    path: String,
+    /// `false` if nothing was saved yet, sizes are `0` then.
+    exists: bool,
+    bytes: i64,
+    /// Keys saved, over all sections.
+    entries: i64,
+    /// Whether `warn_bytes` or `warn_entries` is exceeded.
+    over_budget: bool,
+}
+
+/// Reports what each subsystem persists under `user://`, for spotting save bloat in a debug
+/// screen before players hit cloud-save limits.
+#[derive(NativeClass)]
+#[inherit(Reference)]
+pub struct StorageReport {
+    /// `{ subsystem: path }` of the ConfigFiles to report, the autoloads' default `save_path`s.
+    #[property]
+    saves: HashMap<String, String>,
+    /// Size in bytes above which a save is reported as over budget, `0` for no limit.
+    #[property]
+    warn_bytes: i64,
+    /// Number of keys above which a save is reported as over budget, `0` for no limit.
+    #[property]
+    warn_entries: i64,
+}
+
+#[methods]
+impl StorageReport {
+    fn new(_owner: &Reference) -> Self {
+        let saves = [
+            ("flags", "user://game_flags.cfg"),
+            ("gacha", "user://gacha.cfg"),
+            ("settings", "user://settings.cfg"),
+            ("tutorial", "user://tutorial.cfg"),
+        ];
+        StorageReport {
+            saves: saves
+                .iter()
+                .map(|(subsystem, path)| (subsystem.to_string(), path.to_string()))
+                .collect(),
+            warn_bytes: 64 * 1024,
+            warn_entries: 1000,
+        }
+    }
+
+    /// One `{ subsystem, path, exists, bytes, entries, over_budget }` per save, by subsystem
+    /// name. Saves over budget are also logged as warnings.
+    #[method]
+    fn storage_report(&self) -> Vec<SaveUsage> {
+        let mut subsystems: Vec<_> = self.saves.iter().collect();
+        subsystems.sort();
+        subsystems
+            .into_iter()
+            .map(|(subsystem, path)| {
+                let usage = self.usage(subsystem, path);
+                if usage.over_budget {
+                    godot_warn!(
+                        "{subsystem} save {path} is over budget: {} bytes, {} entries",
+                        usage.bytes,
+                        usage.entries
+                    );
+                }
+                usage
+            })
+            .collect()
+    }
+
+    fn usage(&self, subsystem: &str, path: &str) -> SaveUsage {
+        let mut usage = SaveUsage {
+            subsystem: subsystem.to_string(),
+            path: path.to_string(),
+            exists: false,
+            bytes: 0,
+            entries: 0,
+            over_budget: false,
+        };
+        let file = File::new();
+        match file.open(path, File::READ) {
+            Ok(()) => {
+                usage.exists = true;
+                usage.bytes = file.get_len();
+                file.close();
+            }
+            Err(GodotError::FileNotFound) => return usage,
+            Err(e) => {
+                godot_error!("could not open {subsystem} save {path}: {e}");
+                return usage;
+            }
+        }
+        let config = ConfigFile::new();
+        if let Err(e) = config.load(path) {
+            godot_error!("could not load {subsystem} save {path}: {e}");
+        }
+        usage.entries = config
+            .get_sections()
+            .to_vec()
+            .into_iter()
+            .map(|section| i64::from(config.get_section_keys(section).len()))
+            .sum();
+        usage.over_budget = self.over_budget(usage.bytes, usage.entries);
+        usage
+    }
+
+    fn over_budget(&self, bytes: i64, entries: i64) -> bool {
+        let exceeds = |value: i64, limit: i64| limit > 0 && value > limit;
+        exceeds(bytes, self.warn_bytes) || exceeds(entries, self.warn_entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StorageReport;
+    use std::collections::HashMap;
+
+    #[test]
+    fn budgets() {
+        let report = StorageReport {
+            saves: HashMap::new(),
+            warn_bytes: 100,
+            warn_entries: 0,
+        };
+        assert!(!report.over_budget(100, 5000));
+        assert!(report.over_budget(101, 0));
+        let report = StorageReport {
+            warn_entries: 10,
+            ..report
+        };
+        assert!(report.over_budget(0, 11));
+        assert!(!report.over_budget(0, 10));
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::{BTreeSet, HashMap};
+
+use gdnative::api::ConfigFile;
+use gdnative::core_types::GodotError;
+use gdnative::prelude::*;
+
+/// ConfigFile section holding the completed steps.
+const COMPLETED: &str = "completed";
+
+/// Onboarding progress: which tutorial steps are done and which actions they unlock.
+///
+/// Steps listed in `steps` are shown in that order, other steps whenever they aren't done.
+/// `gates` maps an action to the step that has to be completed before it's allowed, e.g.
+/// `{ "standard_pull": "first_pull" }` keeps the standard banner locked until the scripted
+/// first pull is done. Progress is loaded from `save_path` on `_ready` and saved on every change.
+#[derive(NativeClass, Debug, Default)]
+#[inherit(Node)]
+#[register_with(Self::register)]
+pub struct TutorialTracker {
+    /// Ordered tutorial steps, a step is only shown once the ones before it are completed.
+    #[property]
+    steps: Vec<String>,
+    /// `{ action: step }` pairs, the action is blocked until the step is completed.
+    #[property]
+    gates: HashMap<String, String>,
+    /// ConfigFile the progress is kept in.
+    #[property]
+    save_path: String,
+    completed: BTreeSet<String>,
+}
+
+#[methods]
+impl TutorialTracker {
+    fn new(_owner: &Node) -> Self {
+        TutorialTracker {
+            save_path: "user://tutorial.cfg".into(),
+            ..Default::default()
+        }
+    }
+
+    fn register(builder: &ClassBuilder<Self>) {
+        builder
+            .signal("step_completed")
+            .with_param("step", VariantType::GodotString)
+            .done();
+        builder.signal("tutorial_finished").done();
+    }
+
+    #[method]
+    fn _ready(&mut self) {
+        self.load_progress();
+    }
+
+    /// Whether the tutorial for `step` should be shown now.
+    #[method]
+    fn should_show(&self, step: String) -> bool {
+        if self.completed.contains(&step) {
+            return false;
+        }
+        match self.steps.iter().position(|s| *s == step) {
+            Some(idx) => self.steps[..idx].iter().all(|s| self.completed.contains(s)),
+            None => true,
+        }
+    }
+
+    #[method]
+    fn is_completed(&self, step: String) -> bool {
+        self.completed.contains(&step)
+    }
+
+    /// Mark `step` as done, emitting `step_completed` and `tutorial_finished` once all `steps`
+    /// are. Returns `false` if it was already completed.
+    #[method]
+    fn complete(&mut self, #[base] owner: &Node, step: String) -> bool {
+        if !self.completed.insert(step.clone()) {
+            return false;
+        }
+        self.save_progress();
+        owner.emit_signal("step_completed", &[step.to_variant()]);
+        if self.steps.contains(&step) && self.is_finished() {
+            owner.emit_signal("tutorial_finished", &[]);
+        }
+        true
+    }
+
+    /// Whether `action` is unlocked, actions without a gate always are.
+    #[method]
+    fn is_allowed(&self, action: String) -> bool {
+        self.gates
+            .get(&action)
+            .is_none_or(|step| self.completed.contains(step))
+    }
+
+    /// Whether every step in `steps` is completed.
+    #[method]
+    fn is_finished(&self) -> bool {
+        self.steps.iter().all(|step| self.completed.contains(step))
+    }
+
+    /// Forget all progress, e.g. for a "replay tutorial" option.
+    #[method]
+    fn reset(&mut self) {
+        self.completed.clear();
+        self.save_progress();
+    }
+
+    fn save_progress(&self) {
+        let file = ConfigFile::new();
+        for step in &self.completed {
+            file.set_value(COMPLETED, step.as_str(), true);
+        }
+        if let Err(e) = file.save(self.save_path.as_str()) {
+            godot_error!(
+                "could not save tutorial progress to {}: {e}",
+                self.save_path
+            );
+        }
+    }
+
+    fn load_progress(&mut self) {
+        let file = ConfigFile::new();
+        match file.load(self.save_path.as_str()) {
+            Ok(()) => {}
+            Err(GodotError::FileNotFound) => return,
+            Err(e) => {
+                godot_error!(
+                    "could not load tutorial progress from {}: {e}",
+                    self.save_path
+                );
+                return;
+            }
+        }
+        if file.has_section(COMPLETED) {
+            self.completed = file
+                .get_section_keys(COMPLETED)
+                .to_vec()
+                .iter()
+                .map(|step| step.to_string())
+                .collect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TutorialTracker;
+    use std::collections::HashMap;
+
+    fn tracker() -> TutorialTracker {
+        TutorialTracker {
+            steps: vec!["intro".into(), "first_pull".into(), "inventory".into()],
+            gates: HashMap::from([("standard_pull".into(), "first_pull".into())]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn steps_in_order() {
+        let mut tracker = tracker();
+        assert!(tracker.should_show("intro".into()));
+        assert!(!tracker.should_show("first_pull".into()));
+        assert!(tracker.should_show("shop".into()));
+
+        tracker.completed.insert("intro".into());
+        assert!(!tracker.should_show("intro".into()));
+        assert!(tracker.should_show("first_pull".into()));
+        assert!(!tracker.should_show("inventory".into()));
+        assert!(!tracker.is_finished());
+
+        tracker.completed.insert("first_pull".into());
+        tracker.completed.insert("inventory".into());
+        assert!(tracker.is_finished());
+    }
+
+    #[test]
+    fn gates() {
+        let mut tracker = tracker();
+        assert!(!tracker.is_allowed("standard_pull".into()));
+        assert!(tracker.is_allowed("open_shop".into()));
+
+        tracker.completed.insert("first_pull".into());
+        assert!(tracker.is_allowed("standard_pull".into()));
+    }
+}